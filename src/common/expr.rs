@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::fmt::Display;
 
-use crate::common::{Operator, Value, ValueError};
+use crate::common::{Operator, Type, Value, ValueError};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
@@ -8,6 +8,44 @@ pub enum Expr {
     Variable(String),
     Unary(Operator, Box<Self>),
     Binary(Box<Self>, Operator, Box<Self>),
+    Call(String, Vec<Self>),
+    Index(Box<Self>, Vec<Self>),
+    Field(Box<Self>, String),
+    AddressOf(Box<Self>),
+    Deref(Box<Self>),
+    New(Type, Vec<Self>),
+    Method(Box<Self>, String, Vec<Self>),
+    Super,
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |exprs: &[Expr]| {
+            exprs
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Expr::Literal(v) => write!(f, "{}", v),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Unary(op, a) => write!(f, "{} {}", op, a),
+            Expr::Binary(a, op, b) => write!(f, "({} {} {})", a, op, b),
+            Expr::Call(name, args) => write!(f, "{}({})", name, list(args)),
+            Expr::Index(base, indices) => write!(f, "{}[{}]", base, list(indices)),
+            Expr::Field(base, field) => write!(f, "{}.{}", base, field),
+            Expr::AddressOf(a) => write!(f, "@{}", a),
+            Expr::Deref(a) => write!(f, "{}^", a),
+            Expr::New(ty, args) if args.is_empty() => write!(f, "NEW {}", ty),
+            Expr::New(ty, args) => write!(f, "NEW {}({})", ty, list(args)),
+            Expr::Method(object, method, args) => {
+                write!(f, "{}.{}({})", object, method, list(args))
+            }
+            Expr::Super => write!(f, "SUPER"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -17,48 +55,23 @@ pub enum Error {
 }
 
 impl Expr {
-    pub fn eval(&self, env: &HashMap<String, Value>) -> Result<Value, Error> {
-        match self {
-            Self::Literal(v) => Ok(v.clone()),
-
-            Self::Variable(name) => match env.get(name) {
-                Some(v) => Ok(v.clone()),
-                None => return Err(Error::VariableNotFound(name.clone())),
-            },
-
-            Self::Unary(op, a) => {
-                let a = a.eval(env)?;
-
-                match op {
-                    Operator::Minus => a.neg(),
-                    Operator::Not => a.not(),
-                    _ => unreachable!(),
-                }
-            }
-
-            Self::Binary(a, op, b) => {
-                let a = a.eval(env)?;
-                let b = b.eval(env)?;
+    /// Value of an expression built only from literals and operators, worked out
+    /// without running the program. `None` if it depends on anything else or fails.
+    pub fn fold(&self) -> Option<Value> {
+        self.fold_with(&|_| None)
+    }
 
-                match op {
-                    Operator::Plus => a.add(&b),
-                    Operator::Minus => a.sub(&b),
-                    Operator::Divide => a.div(&b),
-                    Operator::Multiply => a.mul(&b),
-                    Operator::Quotient => a.quot(&b),
-                    Operator::Remainder => a.modu(&b),
-                    Operator::And => a.and(&b),
-                    Operator::Or => a.or(&b),
-                    Operator::Gt => a.gt(&b),
-                    Operator::Lt => a.lt(&b),
-                    Operator::Ge => a.ge(&b),
-                    Operator::Le => a.le(&b),
-                    Operator::Eq => a.eq(&b),
-                    Operator::Ne => a.ne(&b),
-                    _ => unreachable!(),
-                }
-            }
+    /// Like [`Expr::fold`], but the expression may also use names for which
+    /// `constant` gives a value
+    pub fn fold_with(&self, constant: &dyn Fn(&str) -> Option<Value>) -> Option<Value> {
+        match self {
+            Self::Literal(v) => Some(v.clone()),
+            Self::Variable(name) => constant(name),
+            Self::Unary(op, a) => op.apply_unary(&a.fold_with(constant)?).ok(),
+            Self::Binary(a, op, b) => op
+                .apply(&a.fold_with(constant)?, &b.fold_with(constant)?)
+                .ok(),
+            _ => None,
         }
-        .map_err(Error::ValueError)
     }
 }
@@ -1,10 +1,14 @@
 pub mod expr;
 pub mod op;
+pub mod place;
 pub mod stmt;
+pub mod ty;
 pub mod val;
 
 // Re-Exports
 pub use expr::{Error as EvalError, Expr};
 pub use op::Operator;
-pub use stmt::Stmt;
-pub use val::{Error as ValueError, Value};
+pub use place::{Address, Place, Root};
+pub use stmt::{Access, CaseLabel, Class, FileMode, Method, Param, PassBy, Stmt};
+pub use ty::{Bound, Type, TypeDef};
+pub use val::{Array, Enum, Error as ValueError, Object, Pointer, Record, Set, Value};
@@ -1,3 +1,7 @@
+use std::fmt::Display;
+
+use super::{Value, ValueError};
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Operator {
     // Arithmetic
@@ -20,4 +24,74 @@ pub enum Operator {
     Le,
     Eq,
     Ne,
+
+    // Set
+    In,
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Divide => "/",
+            Operator::Multiply => "*",
+            Operator::Quotient => "DIV",
+            Operator::Remainder => "MOD",
+            Operator::And => "AND",
+            Operator::Or => "OR",
+            Operator::Not => "NOT",
+            Operator::Gt => ">",
+            Operator::Lt => "<",
+            Operator::Ge => ">=",
+            Operator::Le => "<=",
+            Operator::Eq => "=",
+            Operator::Ne => "<>",
+            Operator::In => "IN",
+            Operator::Union => "UNION",
+            Operator::Intersection => "INTERSECTION",
+            Operator::Difference => "DIFFERENCE",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl Operator {
+    /// Applies a prefix operator to `a`
+    pub fn apply_unary(self, a: &Value) -> Result<Value, ValueError> {
+        match self {
+            Operator::Minus => a.neg(),
+            Operator::Not => a.not(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Applies an infix operator to `a` and `b`
+    pub fn apply(self, a: &Value, b: &Value) -> Result<Value, ValueError> {
+        match self {
+            Operator::Plus => a.add(b),
+            Operator::Minus => a.sub(b),
+            Operator::Divide => a.div(b),
+            Operator::Multiply => a.mul(b),
+            Operator::Quotient => a.quot(b),
+            Operator::Remainder => a.modu(b),
+            Operator::And => a.and(b),
+            Operator::Or => a.or(b),
+            Operator::Gt => a.gt(b),
+            Operator::Lt => a.lt(b),
+            Operator::Ge => a.ge(b),
+            Operator::Le => a.le(b),
+            Operator::Eq => a.eq(b),
+            Operator::Ne => a.ne(b),
+            Operator::In => a.is_in(b),
+            Operator::Union => a.union(b),
+            Operator::Intersection => a.intersection(b),
+            Operator::Difference => a.difference(b),
+            Operator::Not => unreachable!(),
+        }
+    }
 }
@@ -0,0 +1,42 @@
+use std::fmt::Display;
+
+/// Where the storage of a place lives: a variable's slot, or a cell on the heap
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Root {
+    Slot(usize),
+    Heap(usize),
+}
+
+impl Display for Root {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Root::Slot(slot) => write!(f, "{}", slot),
+            Root::Heap(cell) => write!(f, "heap:{}", cell),
+        }
+    }
+}
+
+/// A storage location: a variable's slot or heap cell plus the path of array
+/// elements (and, for composite types, fields) leading into it
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Place {
+    pub root: Root,
+    pub path: Vec<usize>,
+}
+
+impl Place {
+    pub fn new(slot: usize) -> Self {
+        Self {
+            root: Root::Slot(slot),
+            path: Vec::new(),
+        }
+    }
+}
+
+/// A place as held by a pointer. Slots are reused once their scope ends, so the
+/// address remembers which declaration it was taken from to catch dangling pointers.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Address {
+    pub place: Place,
+    pub(crate) stamp: u64,
+}
@@ -1,10 +1,100 @@
-use super::Expr;
+use super::{Expr, Type, TypeDef, Value, ValueError};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Expression(Expr),
-    Declare(String, String),
-    Assign(String, Expr),
+    Declare(String, Type),
+    /// A CONSTANT, whose value the parser has already worked out into a literal
+    Constant(String, Expr),
+    TypeDef(String, TypeDef),
+    Define(String, Vec<Expr>, String),
+    Assign(Expr, Expr),
     Output(Vec<Expr>),
-    Input(String),
+    Input(Expr),
+    If(Expr, Vec<Stmt>, Option<Vec<Stmt>>),
+    For(String, Expr, Expr, Option<Expr>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Repeat(Vec<Stmt>, Expr),
+    Case(Expr, Vec<(CaseLabel, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    Procedure(String, Vec<Param>, Vec<Stmt>),
+    Function(String, Vec<Param>, Type, Vec<Stmt>),
+    Call(String, Vec<Expr>),
+    Return(Expr),
+    OpenFile(Expr, FileMode),
+    ReadFile(Expr, Expr),
+    WriteFile(Expr, Expr),
+    CloseFile(Expr),
+    Seek(Expr, Expr),
+    GetRecord(Expr, Expr),
+    PutRecord(Expr, Expr),
+    Class(String, Class),
+    CallMethod(Expr, String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub enum CaseLabel {
+    Value(Expr),
+    Range(Expr, Expr),
+}
+
+impl CaseLabel {
+    /// The lowest and highest values this label matches, if they are known before running
+    pub fn fold(&self) -> Option<(Value, Value)> {
+        match self {
+            CaseLabel::Value(v) => v.fold().map(|v| (v.clone(), v)),
+            CaseLabel::Range(lo, hi) => Some((lo.fold()?, hi.fold()?)),
+        }
+    }
+
+    /// Whether the inclusive ranges `a` and `b` share any value
+    pub fn overlap(a: &(Value, Value), b: &(Value, Value)) -> Result<bool, ValueError> {
+        let (lo, hi) = a;
+        let (other_lo, other_hi) = b;
+        Ok(lo.le(other_hi)?.try_as_boolean()? && other_lo.le(hi)?.try_as_boolean()?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub mode: PassBy,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PassBy {
+    Value,
+    Reference,
+}
+
+/// Body of a CLASS, listing only the members it declares itself
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub parent: Option<String>,
+    pub attributes: Vec<(Access, String, Type)>,
+    pub methods: Vec<Method>,
+}
+
+/// A PROCEDURE, or a FUNCTION when it has a return type, declared within a CLASS
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub access: Access,
+    pub name: String,
+    pub params: Vec<Param>,
+    pub returns: Option<Type>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Private,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileMode {
+    Read,
+    Write,
+    Append,
+    Random,
 }
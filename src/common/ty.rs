@@ -0,0 +1,148 @@
+use std::{collections::HashMap, fmt::Display};
+
+use time::macros::date;
+
+use super::{
+    val::{Array, Enum, Object, Pointer, Record, Set},
+    Value,
+};
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Type {
+    Integer,
+    Real,
+    Char,
+    String,
+    Boolean,
+    Date,
+    Array(Vec<(i64, i64)>, Box<Type>),
+    /// An ARRAY with a bound that names a CONSTANT, as written. The parser replaces it
+    /// with an `Array` once it knows the CONSTANT's value.
+    ArrayOf(Vec<(Bound, Bound)>, Box<Type>),
+    Pointer(Box<Type>),
+    Named(String),
+}
+
+/// One end of a dimension of an ARRAY as written
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Bound {
+    Integer(i64),
+    Constant(String),
+}
+
+/// Body of a user-defined TYPE
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDef {
+    Record(Vec<(String, Type)>),
+    Enum(Vec<String>),
+    Pointer(Type),
+    Set(Type),
+    /// A CLASS, whose members the interpreter keeps track of itself
+    Class,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Integer => write!(f, "INTEGER"),
+            Type::Real => write!(f, "REAL"),
+            Type::Char => write!(f, "CHAR"),
+            Type::String => write!(f, "STRING"),
+            Type::Boolean => write!(f, "BOOLEAN"),
+            Type::Date => write!(f, "DATE"),
+            Type::Array(bounds, ty) => write!(
+                f,
+                "ARRAY[{}] OF {}",
+                bounds
+                    .iter()
+                    .map(|(lo, hi)| format!("{}:{}", lo, hi))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ty
+            ),
+            Type::ArrayOf(bounds, ty) => write!(
+                f,
+                "ARRAY[{}] OF {}",
+                bounds
+                    .iter()
+                    .map(|(lo, hi)| format!("{}:{}", lo, hi))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ty
+            ),
+            Type::Pointer(ty) => write!(f, "^{}", ty),
+            Type::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::Integer(i) => write!(f, "{}", i),
+            Bound::Constant(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Type {
+    /// Value a variable of this type holds between declaration and first assignment,
+    /// or `None` if it names a type missing from `defs` or has unresolved bounds
+    pub fn default_value(&self, defs: &HashMap<String, TypeDef>) -> Option<Value> {
+        Some(match self {
+            Type::Integer => Value::Integer(0),
+            Type::Real => Value::Real(0.0),
+            Type::Char => Value::Character(' '),
+            Type::String => Value::String(String::new()),
+            Type::Boolean => Value::Boolean(false),
+            Type::Date => Value::Date(date!(1970 - 01 - 01)),
+            Type::Array(bounds, ty) => Value::Array(Array::new(
+                bounds.clone(),
+                *ty.clone(),
+                ty.default_value(defs)?,
+            )),
+            Type::ArrayOf(..) => return None,
+            Type::Pointer(ty) => Value::Pointer(Pointer::null(*ty.clone())),
+            Type::Named(name) => match defs.get(name)? {
+                TypeDef::Record(fields) => Value::Record(Record {
+                    ty: name.clone(),
+                    fields: fields
+                        .iter()
+                        .map(|(field, ty)| Some((field.clone(), ty.default_value(defs)?)))
+                        .collect::<Option<_>>()?,
+                }),
+                TypeDef::Enum(members) => Value::Enum(Enum {
+                    ty: name.clone(),
+                    member: members.first()?.clone(),
+                    ordinal: 0,
+                }),
+                TypeDef::Pointer(ty) => Value::Pointer(Pointer::null(ty.resolve(defs))),
+                TypeDef::Set(base) => Value::Set(Set::new(name.clone(), base.clone())),
+                TypeDef::Class => Value::Object(Object::null(name.clone())),
+            },
+        })
+    }
+
+    /// Expands the names of pointer types into the pointer types they stand for.
+    /// Records and enums keep their names, as they are told apart by name.
+    pub fn resolve(&self, defs: &HashMap<String, TypeDef>) -> Type {
+        match self {
+            Type::Array(bounds, ty) => Type::Array(bounds.clone(), Box::new(ty.resolve(defs))),
+            Type::Pointer(ty) => Type::Pointer(Box::new(ty.resolve(defs))),
+            Type::Named(name) => match defs.get(name) {
+                Some(TypeDef::Pointer(ty)) => Type::Pointer(Box::new(ty.resolve(defs))),
+                _ => self.clone(),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// Checks `value` can be stored in a slot of this type, widening INTEGER to REAL
+    pub fn coerce(&self, value: Value) -> Option<Value> {
+        match (self, value) {
+            (Type::Real, Value::Integer(i)) => Some(Value::Real(i as f64)),
+            (ty, value) if value.ty() == *ty => Some(value),
+            _ => None,
+        }
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use time::Date;
 
+use super::{Address, Type};
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Value {
     Integer(i64),
@@ -10,6 +12,142 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Date(Date),
+    Array(Array),
+    Record(Record),
+    Enum(Enum),
+    Pointer(Pointer),
+    Set(Set),
+    Object(Object),
+}
+
+/// Elements of a (possibly multi-dimensional) array, stored in row-major order
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Array {
+    pub bounds: Vec<(i64, i64)>,
+    pub ty: Type,
+    pub elements: Vec<Value>,
+}
+
+/// Instance of a user-defined record TYPE, with fields in declaration order
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Record {
+    pub ty: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+/// Member of a user-defined enumerated TYPE, ordered by its position in the definition
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Enum {
+    pub ty: String,
+    pub member: String,
+    pub ordinal: usize,
+}
+
+/// Reference to a variable or heap cell holding a value of type `ty`, or null
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Pointer {
+    pub ty: Type,
+    pub target: Option<Address>,
+}
+
+/// Value of a SET OF type: distinct elements of `base`, kept in the order they were added
+#[derive(Debug, Clone, PartialOrd)]
+pub struct Set {
+    pub ty: String,
+    pub base: Type,
+    pub elements: Vec<Value>,
+}
+
+impl Set {
+    pub fn new(ty: String, base: Type) -> Self {
+        Self {
+            ty,
+            base,
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, value: &Value) -> bool {
+        self.elements.contains(value)
+    }
+
+    pub fn insert(&mut self, value: Value) {
+        if !self.contains(&value) {
+            self.elements.push(value);
+        }
+    }
+}
+
+/// Sets are equal when they hold the same elements, whatever order those were added in
+impl PartialEq for Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty == other.ty
+            && self.elements.len() == other.elements.len()
+            && self.elements.iter().all(|e| other.contains(e))
+    }
+}
+
+/// Reference to an instance of a CLASS, whose attributes live in a heap cell, or null
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Object {
+    pub class: String,
+    pub cell: Option<usize>,
+}
+
+impl Object {
+    pub fn null(class: String) -> Self {
+        Self { class, cell: None }
+    }
+}
+
+impl Pointer {
+    pub fn null(ty: Type) -> Self {
+        Self { ty, target: None }
+    }
+}
+
+impl Record {
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|(field, _)| field == name)
+    }
+}
+
+impl Array {
+    /// Most elements an array may have, keeping a mistyped bound from exhausting memory
+    pub const MAX_LEN: usize = 1 << 24;
+
+    /// Bounds must describe an array no longer than [`Array::MAX_LEN`], as checked by [`Array::len`]
+    pub fn new(bounds: Vec<(i64, i64)>, ty: Type, fill: Value) -> Self {
+        let len = Self::len(&bounds).expect("array bounds are checked when parsed");
+
+        Self {
+            elements: vec![fill; len],
+            bounds,
+            ty,
+        }
+    }
+
+    /// Number of elements in an array with `bounds`, or `None` if it would have more
+    /// than [`Array::MAX_LEN`] or a lower bound above its upper bound
+    pub fn len(bounds: &[(i64, i64)]) -> Option<usize> {
+        bounds
+            .iter()
+            .try_fold(1usize, |len, (lo, hi)| {
+                let extent = hi.checked_sub(*lo)?.checked_add(1)?;
+                len.checked_mul(usize::try_from(extent).ok()?)
+            })
+            .filter(|&len| len <= Self::MAX_LEN)
+    }
+
+    /// Position in `elements` of the element at `indices`, assuming they are within bounds
+    pub fn offset(&self, indices: &[i64]) -> usize {
+        self.bounds
+            .iter()
+            .zip(indices)
+            .fold(0, |offset, ((lo, hi), i)| {
+                offset * (hi - lo + 1) as usize + (i - lo) as usize
+            })
+    }
 }
 
 impl Display for Value {
@@ -20,7 +158,79 @@ impl Display for Value {
             Value::Character(c) => write!(f, "{}", c),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
-            Value::Date(d) => write!(f, "{} {} {}", d.day(), d.month(), d.year()),
+            Value::Date(d) => write!(f, "{:02}/{:02}/{:04}", d.day(), d.month() as u8, d.year()),
+            Value::Array(a) => write!(
+                f,
+                "[{}]",
+                a.elements
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Record(r) => write!(
+                f,
+                "{{{}}}",
+                r.fields
+                    .iter()
+                    .map(|(field, v)| format!("{}: {}", field, v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Enum(e) => write!(f, "{}", e.member),
+            Value::Pointer(p) => match &p.target {
+                Some(address) => write!(f, "@{}", address.place.root),
+                None => write!(f, "NULL"),
+            },
+            Value::Set(s) => write!(
+                f,
+                "({})",
+                s.elements
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Object(o) => match o.cell {
+                Some(_) => write!(f, "<{}>", o.class),
+                None => write!(f, "NULL"),
+            },
+        }
+    }
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Integer(_) => Type::Integer,
+            Value::Real(_) => Type::Real,
+            Value::Character(_) => Type::Char,
+            Value::String(_) => Type::String,
+            Value::Boolean(_) => Type::Boolean,
+            Value::Date(_) => Type::Date,
+            Value::Array(a) => Type::Array(a.bounds.clone(), Box::new(a.ty.clone())),
+            Value::Record(r) => Type::Named(r.ty.clone()),
+            Value::Enum(e) => Type::Named(e.ty.clone()),
+            Value::Pointer(p) => Type::Pointer(Box::new(p.ty.clone())),
+            Value::Set(s) => Type::Named(s.ty.clone()),
+            Value::Object(o) => Type::Named(o.class.clone()),
+        }
+    }
+
+    /// Component `i` of a composite value
+    pub fn element(&self, i: usize) -> Option<&Value> {
+        match self {
+            Value::Array(a) => a.elements.get(i),
+            Value::Record(r) => r.fields.get(i).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn element_mut(&mut self, i: usize) -> Option<&mut Value> {
+        match self {
+            Value::Array(a) => a.elements.get_mut(i),
+            Value::Record(r) => r.fields.get_mut(i).map(|(_, v)| v),
+            _ => None,
         }
     }
 }
@@ -29,48 +239,52 @@ impl Display for Value {
 pub enum Error {
     InvalidType,
     InvalidOperation,
+    DateOutOfRange,
+    /// INTEGER arithmetic with a result too large for an INTEGER
+    Overflow,
+    DivisionByZero,
 }
 
 pub type ValueResult<T> = Result<T, Error>;
 
 // Try As Methods
 impl Value {
-    fn try_as_integer(&self) -> ValueResult<i64> {
+    pub fn try_as_integer(&self) -> ValueResult<i64> {
         match self {
             Self::Integer(i) => Ok(*i),
             _ => Err(Error::InvalidType),
         }
     }
 
-    fn try_as_real(&self) -> ValueResult<f64> {
+    pub fn try_as_real(&self) -> ValueResult<f64> {
         match self {
             Self::Real(r) => Ok(*r),
             _ => Err(Error::InvalidType),
         }
     }
 
-    fn try_as_character(&self) -> ValueResult<char> {
+    pub fn try_as_character(&self) -> ValueResult<char> {
         match self {
             Self::Character(c) => Ok(*c),
             _ => Err(Error::InvalidType),
         }
     }
 
-    fn try_as_string(&self) -> ValueResult<String> {
+    pub fn try_as_string(&self) -> ValueResult<String> {
         match self {
             Self::String(s) => Ok(s.clone()),
             _ => Err(Error::InvalidType),
         }
     }
 
-    fn try_as_boolean(&self) -> ValueResult<bool> {
+    pub fn try_as_boolean(&self) -> ValueResult<bool> {
         match self {
             Self::Boolean(b) => Ok(*b),
             _ => Err(Error::InvalidType),
         }
     }
 
-    fn try_as_date(&self) -> ValueResult<Date> {
+    pub fn try_as_date(&self) -> ValueResult<Date> {
         match self {
             Self::Date(d) => Ok(*d),
             _ => Err(Error::InvalidType),
@@ -82,7 +296,7 @@ impl Value {
 impl Value {
     pub fn neg(&self) -> ValueResult<Value> {
         Ok(match self {
-            Self::Integer(i) => Value::Integer(-i),
+            Self::Integer(i) => Value::Integer(i.checked_neg().ok_or(Error::Overflow)?),
             Self::Real(r) => Value::Real(-r),
             _ => return Err(Error::InvalidOperation),
         })
@@ -98,8 +312,14 @@ impl Value {
     pub fn add(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
-                Self::Integer(b) => Value::Integer(a + b),
+                Self::Integer(b) => Value::Integer(a.checked_add(*b).ok_or(Error::Overflow)?),
                 Self::Real(b) => Value::Real(*a as f64 + b),
+                Self::Date(b) => add_days(b, *a)?,
+                _ => return Err(Error::InvalidOperation),
+            },
+
+            Self::Date(a) => match rhs {
+                Self::Integer(b) => add_days(a, *b)?,
                 _ => return Err(Error::InvalidOperation),
             },
 
@@ -128,7 +348,7 @@ impl Value {
     pub fn sub(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
-                Self::Integer(b) => Value::Integer(a - b),
+                Self::Integer(b) => Value::Integer(a.checked_sub(*b).ok_or(Error::Overflow)?),
                 Self::Real(b) => Value::Real(*a as f64 - b),
                 _ => return Err(Error::InvalidOperation),
             },
@@ -139,6 +359,14 @@ impl Value {
                 _ => return Err(Error::InvalidOperation),
             },
 
+            Self::Date(a) => match rhs {
+                Self::Integer(b) => add_days(a, b.checked_neg().ok_or(Error::DateOutOfRange)?)?,
+                Self::Date(b) => {
+                    Value::Integer(a.to_julian_day() as i64 - b.to_julian_day() as i64)
+                }
+                _ => return Err(Error::InvalidOperation),
+            },
+
             _ => return Err(Error::InvalidOperation),
         })
     }
@@ -146,7 +374,7 @@ impl Value {
     pub fn mul(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
-                Self::Integer(b) => Value::Integer(a * b),
+                Self::Integer(b) => Value::Integer(a.checked_mul(*b).ok_or(Error::Overflow)?),
                 Self::Real(b) => Value::Real(*a as f64 * b),
                 _ => return Err(Error::InvalidOperation),
             },
@@ -182,7 +410,7 @@ impl Value {
     pub fn quot(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
-                Self::Integer(b) => Value::Integer(a / b),
+                Self::Integer(b) => Value::Integer(divide(*a, *b, i64::checked_div)?),
                 Self::Real(b) => Value::Integer((*a as f64 / b) as i64),
                 _ => return Err(Error::InvalidOperation),
             },
@@ -200,7 +428,7 @@ impl Value {
     pub fn modu(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
-                Self::Integer(b) => Value::Integer(a % b),
+                Self::Integer(b) => Value::Integer(divide(*a, *b, i64::checked_rem)?),
                 Self::Real(b) => Value::Real(*a as f64 % b),
                 _ => return Err(Error::InvalidOperation),
             },
@@ -237,6 +465,64 @@ impl Value {
         })
     }
 
+    pub fn is_in(&self, rhs: &Self) -> ValueResult<Value> {
+        let Self::Set(set) = rhs else {
+            return Err(Error::InvalidOperation);
+        };
+        let value = set
+            .base
+            .coerce(self.clone())
+            .ok_or(Error::InvalidOperation)?;
+
+        Ok(Self::Boolean(set.contains(&value)))
+    }
+
+    pub fn union(&self, rhs: &Self) -> ValueResult<Value> {
+        let (a, b) = Self::sets(self, rhs)?;
+        let mut set = a.clone();
+        for e in &b.elements {
+            set.insert(e.clone());
+        }
+
+        Ok(Self::Set(set))
+    }
+
+    pub fn intersection(&self, rhs: &Self) -> ValueResult<Value> {
+        let (a, b) = Self::sets(self, rhs)?;
+
+        Ok(Self::Set(Set {
+            elements: a
+                .elements
+                .iter()
+                .filter(|e| b.contains(e))
+                .cloned()
+                .collect(),
+            ..a.clone()
+        }))
+    }
+
+    pub fn difference(&self, rhs: &Self) -> ValueResult<Value> {
+        let (a, b) = Self::sets(self, rhs)?;
+
+        Ok(Self::Set(Set {
+            elements: a
+                .elements
+                .iter()
+                .filter(|e| !b.contains(e))
+                .cloned()
+                .collect(),
+            ..a.clone()
+        }))
+    }
+
+    /// Operands of a set operator, which must both be sets of the same TYPE
+    fn sets<'a>(lhs: &'a Self, rhs: &'a Self) -> ValueResult<(&'a Set, &'a Set)> {
+        match (lhs, rhs) {
+            (Self::Set(a), Self::Set(b)) if a.ty == b.ty => Ok((a, b)),
+            _ => Err(Error::InvalidOperation),
+        }
+    }
+
     pub fn eq(&self, rhs: &Self) -> ValueResult<Value> {
         Ok(match self {
             Self::Integer(a) => match rhs {
@@ -265,6 +551,23 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a == b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal == b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Pointer(a) => match rhs {
+                Self::Pointer(b) if a.ty == b.ty => Self::Boolean(a.target == b.target),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Set(a) => match rhs {
+                Self::Set(b) if a.ty == b.ty => Self::Boolean(a == b),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Object(a) => match rhs {
+                Self::Object(b) => Self::Boolean(a.cell == b.cell),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 
@@ -296,6 +599,23 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a != b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal != b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Pointer(a) => match rhs {
+                Self::Pointer(b) if a.ty == b.ty => Self::Boolean(a.target != b.target),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Set(a) => match rhs {
+                Self::Set(b) if a.ty == b.ty => Self::Boolean(a != b),
+                _ => return Err(Error::InvalidOperation),
+            },
+            Self::Object(a) => match rhs {
+                Self::Object(b) => Self::Boolean(a.cell != b.cell),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 
@@ -327,6 +647,11 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a > b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal > b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 
@@ -358,6 +683,11 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a < b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal < b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 
@@ -389,6 +719,11 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a >= b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal >= b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 
@@ -420,6 +755,29 @@ impl Value {
                 Self::Date(b) => Self::Boolean(a <= b),
                 _ => return Err(Error::InvalidOperation),
             },
+            Self::Enum(a) => match rhs {
+                Self::Enum(b) if a.ty == b.ty => Self::Boolean(a.ordinal <= b.ordinal),
+                _ => return Err(Error::InvalidOperation),
+            },
+            _ => return Err(Error::InvalidOperation),
         })
     }
 }
+
+/// Divides INTEGERs with `op`, which fails for a zero divisor or a result out of range
+fn divide(a: i64, b: i64, op: fn(i64, i64) -> Option<i64>) -> ValueResult<i64> {
+    match b {
+        0 => Err(Error::DivisionByZero),
+        _ => op(a, b).ok_or(Error::Overflow),
+    }
+}
+
+/// The date `days` days after `date`, or before it if `days` is negative
+fn add_days(date: &Date, days: i64) -> ValueResult<Value> {
+    (date.to_julian_day() as i64)
+        .checked_add(days)
+        .and_then(|day| i32::try_from(day).ok())
+        .and_then(|day| Date::from_julian_day(day).ok())
+        .map(Value::Date)
+        .ok_or(Error::DateOutOfRange)
+}
@@ -0,0 +1,281 @@
+use super::executor::{Error, Interpreter};
+use crate::{
+    common::{Type, Value},
+    parser::number,
+};
+use time::{Date, Month};
+
+/// A library routine, called with its arguments already evaluated
+pub type Builtin = fn(&mut Interpreter, &[Value]) -> Result<Value, Error>;
+
+/// The built-in function called `name`, which takes precedence over any FUNCTION
+/// the program declares with the same name
+pub fn builtin(name: &str) -> Option<Builtin> {
+    Some(match name {
+        // Files
+        "EOF" => eof,
+        // Strings
+        "LEFT" => left,
+        "RIGHT" => right,
+        "MID" => mid,
+        "LENGTH" => length,
+        "LCASE" => lcase,
+        "UCASE" => ucase,
+        "TO_UPPER" => to_upper,
+        "TO_LOWER" => to_lower,
+        // Numbers and conversions
+        "INT" => int,
+        "RAND" => rand,
+        "NUM_TO_STR" => num_to_str,
+        "STR_TO_NUM" => str_to_num,
+        "IS_NUM" => is_num,
+        "CHR" => chr,
+        "ASC" => asc,
+        // Dates
+        "DAY" => day,
+        "MONTH" => month,
+        "YEAR" => year,
+        "DAYINDEX" => day_index,
+        "SETDATE" => set_date,
+        "NOW" => now,
+        _ => return None,
+    })
+}
+
+fn eof(interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [file] = arity(args)?;
+    interpreter.eof(&string(file)?).map(Value::Boolean)
+}
+
+/// `LEFT(ThisString, x)`: the leftmost `x` characters
+fn left(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [s, x] = arity(args)?;
+    substring(&string(s)?, 1, integer(x)?)
+}
+
+/// `RIGHT(ThisString, x)`: the rightmost `x` characters
+fn right(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [s, x] = arity(args)?;
+    let (s, x) = (string(s)?, integer(x)?);
+    let len = s.chars().count() as i64;
+
+    // A start beyond either end of an INTEGER is as far out of range as it gets
+    match len.checked_sub(x).and_then(|start| start.checked_add(1)) {
+        Some(start) => substring(&s, start, x),
+        None => Err(out_of_range(&s, len.saturating_sub(x).saturating_add(1), x)),
+    }
+}
+
+/// `MID(ThisString, x, y)`: `y` characters starting from position `x`
+fn mid(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [s, x, y] = arity(args)?;
+    substring(&string(s)?, integer(x)?, integer(y)?)
+}
+
+fn length(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [s] = arity(args)?;
+    Ok(Value::Integer(string(s)?.chars().count() as i64))
+}
+
+fn lcase(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [c] = arity(args)?;
+    Ok(Value::Character(character(c)?.to_ascii_lowercase()))
+}
+
+fn ucase(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [c] = arity(args)?;
+    Ok(Value::Character(character(c)?.to_ascii_uppercase()))
+}
+
+/// `TO_UPPER(x)`: `x` with its letters in upper case, for a CHAR or a STRING
+fn to_upper(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    match x {
+        Value::Character(c) => Ok(Value::Character(c.to_ascii_uppercase())),
+        x => Ok(Value::String(string(x)?.to_ascii_uppercase())),
+    }
+}
+
+/// `TO_LOWER(x)`: `x` with its letters in lower case, for a CHAR or a STRING
+fn to_lower(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    match x {
+        Value::Character(c) => Ok(Value::Character(c.to_ascii_lowercase())),
+        x => Ok(Value::String(string(x)?.to_ascii_lowercase())),
+    }
+}
+
+/// `INT(x)`: the integer part of `x`, dropping any fraction
+fn int(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    match x {
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        x => Ok(Value::Integer(real(x)?.trunc() as i64)),
+    }
+}
+
+/// `RAND(x)`: a random REAL from 0 up to but not including `x`
+fn rand(interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    let x = real(x)?;
+    Ok(Value::Real(interpreter.random().next_f64() * x))
+}
+
+/// `NUM_TO_STR(x)`: `x` written out as a literal, so a REAL keeps its fractional part and
+/// reads back as a REAL
+fn num_to_str(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    match x {
+        Value::Integer(i) => Ok(Value::String(i.to_string())),
+        Value::Real(r) if r.is_finite() && r.fract() == 0.0 => {
+            Ok(Value::String(format!("{}.0", r)))
+        }
+        Value::Real(r) => Ok(Value::String(r.to_string())),
+        x => Err(mismatch(Type::Real, x)),
+    }
+}
+
+/// `STR_TO_NUM(x)`: the number `x` spells out, an INTEGER or a REAL as it would be
+/// written as a literal
+fn str_to_num(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    let x = string(x)?;
+    number(&x).ok_or(Error::NotANumber(x))
+}
+
+fn is_num(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    Ok(Value::Boolean(number(&string(x)?).is_some()))
+}
+
+/// `CHR(x)`: the character with the code `x`
+fn chr(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    let x = integer(x)?;
+    u32::try_from(x)
+        .ok()
+        .and_then(char::from_u32)
+        .map(Value::Character)
+        .ok_or(Error::InvalidCharacterCode(x))
+}
+
+/// `ASC(x)`: the code of the character `x`
+fn asc(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [x] = arity(args)?;
+    Ok(Value::Integer(character(x)? as i64))
+}
+
+fn day(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [d] = arity(args)?;
+    Ok(Value::Integer(date(d)?.day() as i64))
+}
+
+fn month(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [d] = arity(args)?;
+    Ok(Value::Integer(date(d)?.month() as i64))
+}
+
+fn year(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [d] = arity(args)?;
+    Ok(Value::Integer(date(d)?.year() as i64))
+}
+
+/// `DAYINDEX(x)`: the day of the week `x` falls on, from 1 for Sunday to 7 for Saturday
+fn day_index(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [d] = arity(args)?;
+    Ok(Value::Integer(
+        date(d)?.weekday().number_from_sunday() as i64
+    ))
+}
+
+/// `SETDATE(Day, Month, Year)`: the date with the given day, month and year
+fn set_date(_: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [d, m, y] = arity(args)?;
+    let (day, month, year) = (integer(d)?, integer(m)?, integer(y)?);
+
+    let month_of_year = u8::try_from(month)
+        .ok()
+        .and_then(|m| Month::try_from(m).ok());
+    month_of_year
+        .zip(u8::try_from(day).ok())
+        .zip(i32::try_from(year).ok())
+        .and_then(|((m, d), y)| Date::from_calendar_date(y, m, d).ok())
+        .map(Value::Date)
+        .ok_or(Error::InvalidDate { day, month, year })
+}
+
+fn now(interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+    let [] = arity(args)?;
+    Ok(Value::Date(interpreter.today()))
+}
+
+/// The `length` characters of `s` from position `start`, counting from 1, all of
+/// which must lie within `s`
+fn substring(s: &str, start: i64, length: i64) -> Result<Value, Error> {
+    let end = (start - 1).checked_add(length);
+
+    if start < 1 || length < 0 || end.is_none_or(|end| end > s.chars().count() as i64) {
+        return Err(out_of_range(s, start, length));
+    }
+
+    Ok(Value::String(
+        s.chars()
+            .skip(start as usize - 1)
+            .take(length as usize)
+            .collect(),
+    ))
+}
+
+fn out_of_range(s: &str, start: i64, length: i64) -> Error {
+    Error::SubstringOutOfRange {
+        string: s.to_owned(),
+        start,
+        length,
+    }
+}
+
+/// Checks a built-in was given exactly `N` arguments
+fn arity<const N: usize>(args: &[Value]) -> Result<&[Value; N], Error> {
+    args.try_into().map_err(|_| Error::ArgumentCount {
+        expected: N,
+        found: args.len(),
+    })
+}
+
+fn mismatch(expected: Type, found: &Value) -> Error {
+    Error::TypeMismatch {
+        expected,
+        found: found.ty(),
+    }
+}
+
+fn string(value: &Value) -> Result<String, Error> {
+    value
+        .try_as_string()
+        .map_err(|_| mismatch(Type::String, value))
+}
+
+fn integer(value: &Value) -> Result<i64, Error> {
+    value
+        .try_as_integer()
+        .map_err(|_| mismatch(Type::Integer, value))
+}
+
+/// Reads an INTEGER or REAL argument as a REAL
+fn real(value: &Value) -> Result<f64, Error> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Real(r) => Ok(*r),
+        value => Err(mismatch(Type::Real, value)),
+    }
+}
+
+fn date(value: &Value) -> Result<Date, Error> {
+    value.try_as_date().map_err(|_| mismatch(Type::Date, value))
+}
+
+fn character(value: &Value) -> Result<char, Error> {
+    value
+        .try_as_character()
+        .map_err(|_| mismatch(Type::Char, value))
+}
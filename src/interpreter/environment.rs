@@ -1,42 +1,309 @@
-use crate::common::Value;
+pub use crate::common::{Address, Place, Root};
+use crate::common::{Type, Value};
 use std::collections::HashMap;
 
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub ty: Type,
+    pub value: Value,
+    /// Set for a CONSTANT, whose value never changes after declaration
+    pub constant: bool,
+}
+
+impl Variable {
+    pub fn new(ty: Type, value: Value) -> Self {
+        Self {
+            ty,
+            value,
+            constant: false,
+        }
+    }
+
+    pub fn constant(value: Value) -> Self {
+        Self {
+            ty: value.ty(),
+            value,
+            constant: true,
+        }
+    }
+}
+
+/// Variable storage as a stack of scopes over a single stack of slots.
+///
+/// Each scope owns the slots allocated after it was created, so ending a scope
+/// truncates the slot stack instead of copying anything. Lookup walks outwards
+/// from the innermost scope until it reaches a call frame, then falls through
+/// to the global scope, so a procedure sees its own locals and the globals but
+/// never the locals of whoever called it.
+///
+/// Cells created with NEW live on a separate heap that is never freed, so only
+/// pointers into slots can dangle.
 #[derive(Debug, Clone)]
 pub struct Environment {
-    env: HashMap<String, Value>,
-    parent: Option<Box<Environment>>,
+    slots: Vec<Slot>,
+    scopes: Vec<Scope>,
+    heap: Vec<Variable>,
+    /// Stamp given to the next declaration
+    stamp: u64,
 }
 
-impl Environment {
-    pub fn new() -> Self {
+#[derive(Debug, Clone)]
+struct Slot {
+    var: Variable,
+    stamp: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Scope {
+    names: HashMap<String, Place>,
+    base: usize,
+    frame: bool,
+}
+
+impl Scope {
+    fn new(base: usize, frame: bool) -> Self {
         Self {
-            env: HashMap::new(),
-            parent: None,
+            names: HashMap::new(),
+            base,
+            frame,
         }
     }
+}
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            scopes: vec![Scope::new(0, true)],
+            heap: Vec::new(),
+            stamp: 0,
+        }
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a scope nested inside the current one
     pub fn create_scope(&mut self) {
-        self.parent = Some(Box::new(self.clone()));
-        self.env = HashMap::new();
+        self.scopes.push(Scope::new(self.slots.len(), false));
     }
 
+    /// Opens the scope of a procedure or function call, hiding the caller's locals
+    pub fn create_frame(&mut self) {
+        self.scopes.push(Scope::new(self.slots.len(), true));
+    }
+
+    /// Discards the innermost scope along with every variable declared in it.
+    /// The global scope is never discarded.
     pub fn end_scope(&mut self) {
-        if let Some(parent) = &self.parent {
-            self.env = parent.env.clone();
-            self.parent = None;
+        if self.scopes.len() > 1 {
+            if let Some(scope) = self.scopes.pop() {
+                self.slots.truncate(scope.base);
+            }
         }
     }
 
-    pub fn get_value(&self, var: &str) -> Option<Value> {
-        match self.env.get(var) {
-            Some(val) => Some(val.clone()),
-            None => {
-                if let Some(parent) = &self.parent {
-                    parent.get_value(var)
-                } else {
-                    None
-                }
+    /// Declares `var` in the innermost scope, returning false if the name is already taken there
+    pub fn declare(&mut self, name: &str, var: Variable) -> bool {
+        let slot = self.slots.len();
+        let scope = self.scopes.last_mut().expect("global scope always exists");
+
+        if scope.names.contains_key(name) {
+            return false;
+        }
+
+        scope.names.insert(name.to_owned(), Place::new(slot));
+        self.slots.push(Slot {
+            var,
+            stamp: self.stamp,
+        });
+        self.stamp += 1;
+        true
+    }
+
+    /// Creates a heap cell holding `var`, which lives until the end of the program
+    pub fn allocate(&mut self, var: Variable) -> Place {
+        self.heap.push(var);
+        Place {
+            root: Root::Heap(self.heap.len() - 1),
+            path: Vec::new(),
+        }
+    }
+
+    /// Address a pointer to `place` holds
+    pub fn address(&self, place: &Place) -> Address {
+        let stamp = match place.root {
+            Root::Slot(slot) => self.slots[slot].stamp,
+            Root::Heap(_) => 0,
+        };
+
+        Address {
+            place: place.clone(),
+            stamp,
+        }
+    }
+
+    /// Place `address` points to, or `None` if the variable it was taken from no longer exists
+    pub fn dereference(&self, address: &Address) -> Option<Place> {
+        match address.place.root {
+            Root::Slot(slot) => self
+                .slots
+                .get(slot)
+                .filter(|s| s.stamp == address.stamp)
+                .map(|_| address.place.clone()),
+            Root::Heap(_) => Some(address.place.clone()),
+        }
+    }
+
+    /// Makes `name` in the innermost scope an alias of an existing place, as for BYREF parameters
+    pub fn bind(&mut self, name: &str, place: Place) -> bool {
+        let scope = self.scopes.last_mut().expect("global scope always exists");
+
+        if scope.names.contains_key(name) {
+            return false;
+        }
+
+        scope.names.insert(name.to_owned(), place);
+        true
+    }
+
+    pub fn variable(&self, root: &Root) -> &Variable {
+        match *root {
+            Root::Slot(slot) => &self.slots[slot].var,
+            Root::Heap(cell) => &self.heap[cell],
+        }
+    }
+
+    fn variable_mut(&mut self, root: &Root) -> &mut Variable {
+        match *root {
+            Root::Slot(slot) => &mut self.slots[slot].var,
+            Root::Heap(cell) => &mut self.heap[cell],
+        }
+    }
+
+    /// Place the variable `var` resolves to from the innermost scope
+    pub fn resolve(&self, var: &str) -> Option<&Place> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(place) = scope.names.get(var) {
+                return Some(place);
+            }
+
+            if scope.frame {
+                break;
             }
         }
+
+        self.scopes[0].names.get(var)
+    }
+
+    pub fn value(&self, place: &Place) -> &Value {
+        place
+            .path
+            .iter()
+            .fold(&self.variable(&place.root).value, |v, &i| {
+                v.element(i)
+                    .expect("places are only built from valid paths")
+            })
+    }
+
+    pub fn value_mut(&mut self, place: &Place) -> &mut Value {
+        place
+            .path
+            .iter()
+            .fold(&mut self.variable_mut(&place.root).value, |v, &i| {
+                v.element_mut(i)
+                    .expect("places are only built from valid paths")
+            })
+    }
+
+    pub fn get(&self, var: &str) -> Option<&Value> {
+        self.resolve(var).map(|place| self.value(place))
+    }
+
+    pub fn get_mut(&mut self, var: &str) -> Option<&mut Value> {
+        let place = self.resolve(var)?.clone();
+        Some(self.value_mut(&place))
+    }
+
+    pub fn get_value(&self, var: &str) -> Option<Value> {
+        self.get(var).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(env: &mut Environment, name: &str, i: i64) {
+        assert!(env.declare(name, Variable::new(Type::Integer, Value::Integer(i))));
+    }
+
+    #[test]
+    fn nested_scopes_see_outer_scopes() {
+        let mut env = Environment::new();
+        int(&mut env, "a", 1);
+        env.create_scope();
+        int(&mut env, "b", 2);
+        env.create_scope();
+
+        assert_eq!(env.get_value("a"), Some(Value::Integer(1)));
+        assert_eq!(env.get_value("b"), Some(Value::Integer(2)));
+    }
+
+    #[test]
+    fn end_scope_keeps_outer_mutations() {
+        let mut env = Environment::new();
+        int(&mut env, "a", 1);
+        env.create_scope();
+        int(&mut env, "b", 2);
+        *env.get_mut("a").unwrap() = Value::Integer(10);
+        env.create_scope();
+        env.end_scope();
+        env.end_scope();
+
+        assert_eq!(env.get_value("a"), Some(Value::Integer(10)));
+        assert_eq!(env.get_value("b"), None);
+    }
+
+    #[test]
+    fn shadowing_is_undone_by_end_scope() {
+        let mut env = Environment::new();
+        int(&mut env, "a", 1);
+        env.create_scope();
+        int(&mut env, "a", 2);
+
+        assert_eq!(env.get_value("a"), Some(Value::Integer(2)));
+        env.end_scope();
+        assert_eq!(env.get_value("a"), Some(Value::Integer(1)));
+    }
+
+    #[test]
+    fn frames_hide_caller_locals() {
+        let mut env = Environment::new();
+        int(&mut env, "global", 1);
+        env.create_frame();
+        int(&mut env, "caller", 2);
+        env.create_frame();
+
+        assert_eq!(env.get_value("global"), Some(Value::Integer(1)));
+        assert_eq!(env.get_value("caller"), None);
+        env.end_scope();
+        assert_eq!(env.get_value("caller"), Some(Value::Integer(2)));
+    }
+
+    #[test]
+    fn addresses_dangle_once_their_slot_is_reused() {
+        let mut env = Environment::new();
+        env.create_scope();
+        int(&mut env, "a", 1);
+        let address = env.address(env.resolve("a").unwrap());
+        assert!(env.dereference(&address).is_some());
+
+        env.end_scope();
+        int(&mut env, "b", 2);
+        assert!(env.dereference(&address).is_none());
     }
 }
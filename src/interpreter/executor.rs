@@ -1,29 +1,1578 @@
-use crate::common::{EvalError, Stmt, Value};
-use std::collections::HashMap;
+use super::{
+    builtins::builtin,
+    environment::{Environment, Place, Root, Variable},
+    files::{self, DiskFileSystem, FileSystem},
+    io::{InputSource, OutputSink, StdinInput, StdoutSink},
+    random::Random,
+};
+use crate::common::{
+    Access, Array, CaseLabel, Class, Enum, EvalError, Expr, FileMode, Object, Param, PassBy,
+    Pointer, Record, Set, Stmt, Type, TypeDef, Value, ValueError,
+};
+use crate::parser::input_value;
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    rc::Rc,
+};
+use time::{Date, OffsetDateTime};
 
 #[derive(Debug)]
 pub enum Error {
     EvaluationError(EvalError),
+    AlreadyDeclared(String),
+    UndeclaredVariable(String),
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+    },
+    ValueError(ValueError),
+    OverlappingCaseLabels(String, String),
+    ZeroStep,
+    UndefinedProcedure(String),
+    UndefinedFunction(String),
+    NotAProcedure(String),
+    NotAFunction(String),
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+    },
+    NotAReference(String),
+    MissingReturn(String),
+    ReturnOutsideFunction,
+    NotAssignable(String),
+    AssignToConstant(String),
+    NotAnArray(String),
+    IndexCount {
+        array: String,
+        expected: usize,
+        found: usize,
+    },
+    IndexOutOfBounds {
+        array: String,
+        index: i64,
+        bounds: (i64, i64),
+    },
+    UndefinedType(String),
+    NotARecord(String),
+    NoSuchField {
+        record: String,
+        field: String,
+    },
+    DuplicateEnumMember {
+        member: String,
+        first: String,
+        second: String,
+    },
+    CyclicType(String),
+    NotAPointer(String),
+    NullPointer(String),
+    DanglingPointer(String),
+    NotASet(String),
+    EndOfInput,
+    InvalidInput {
+        expected: Type,
+        input: String,
+    },
+    FileError {
+        file: String,
+        error: io::Error,
+    },
+    FileAlreadyOpen(String),
+    FileNotOpen(String),
+    WrongFileMode {
+        file: String,
+        mode: FileMode,
+    },
+    EndOfFile(String),
+    FilesLeftOpen(Vec<String>),
+    InvalidAddress(i64),
+    NotStorable(Type),
+    CorruptRecord(String),
+    NotAnObject(String),
+    NoSuchMethod {
+        class: String,
+        method: String,
+    },
+    PrivateMember {
+        class: String,
+        member: String,
+    },
+    SuperOutsideMethod,
+    NoSuperclass(String),
+    SubstringOutOfRange {
+        string: String,
+        start: i64,
+        length: i64,
+    },
+    NotANumber(String),
+    InvalidCharacterCode(i64),
+    InvalidDate {
+        day: i64,
+        month: i64,
+        year: i64,
+    },
 }
 
-pub fn exec(stmt: &Stmt, env: &mut HashMap<String, Value>) -> Result<Option<Value>, Error> {
-    match stmt {
-        Stmt::Expression(a) => Ok(Some(a.eval(env).map_err(Error::EvaluationError)?)),
-        Stmt::Declare(_, _) => todo!(),
-        Stmt::Assign(_, _) => todo!(),
-        Stmt::Output(vs) => {
-            println!(
-                "{}",
-                vs.iter()
-                    .map(|expr| expr
-                        .eval(env)
-                        .map(|v| v.to_string())
-                        .map_err(Error::EvaluationError))
+/// What a statement asks of the block it is running in
+enum Flow {
+    Continue,
+    Return(Value),
+}
+
+/// Evaluated subscript or field name, picking a component out of a composite value
+enum Selector<'a> {
+    Index(Vec<i64>),
+    Field(&'a str),
+}
+
+/// How a parameter is bound once the call frame is open
+enum Argument {
+    Value(Value),
+    Reference(Place),
+}
+
+/// A file between its OPENFILE and CLOSEFILE
+#[derive(Debug)]
+struct OpenFile {
+    mode: FileMode,
+    /// Lines not yet read, for a file opened for READ
+    lines: VecDeque<String>,
+    /// Index of the record the next GETRECORD or PUTRECORD uses, for a RANDOM file
+    position: usize,
+}
+
+/// A PROCEDURE, or a FUNCTION when it has a return type
+#[derive(Debug)]
+struct Subroutine {
+    params: Vec<Param>,
+    returns: Option<Type>,
+    body: Vec<Stmt>,
+}
+
+/// A subroutine to call, with the object it runs on if it is a method
+type Callee = (Rc<Subroutine>, Option<This>);
+
+/// A CLASS with the attributes it inherits folded in
+#[derive(Debug)]
+struct ClassInfo {
+    parent: Option<String>,
+    /// Attributes in the order an object lays them out, inherited ones first
+    attributes: Vec<Attribute>,
+    /// Methods the class declares itself, overriding any of the same name it inherits
+    methods: HashMap<String, (Access, Rc<Subroutine>)>,
+}
+
+#[derive(Debug, Clone)]
+struct Attribute {
+    name: String,
+    ty: Type,
+    access: Access,
+    /// Class that declared the attribute, the only one whose methods may touch it if PRIVATE
+    owner: String,
+}
+
+/// The object a method was called on, and the class whose declaration of the method is running
+#[derive(Debug, Clone)]
+struct This {
+    object: Object,
+    class: String,
+}
+
+/// Program state that persists across every statement of a run or REPL session
+#[derive(Debug)]
+pub struct Interpreter {
+    env: Environment,
+    subroutines: HashMap<String, Rc<Subroutine>>,
+    types: HashMap<String, TypeDef>,
+    /// Members of every enumerated TYPE, which share one namespace
+    members: HashMap<String, Enum>,
+    input: Box<dyn InputSource>,
+    output: Box<dyn OutputSink>,
+    fs: Box<dyn FileSystem>,
+    files: HashMap<String, OpenFile>,
+    classes: HashMap<String, ClassInfo>,
+    /// Object of each running call, if it is a method call
+    this: Vec<Option<This>>,
+    random: Random,
+    /// Date NOW returns, if the host has pinned it
+    today: Option<Date>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            env: Environment::default(),
+            subroutines: HashMap::new(),
+            types: HashMap::new(),
+            members: HashMap::new(),
+            input: Box::new(StdinInput),
+            output: Box::new(StdoutSink),
+            fs: Box::new(DiskFileSystem),
+            files: HashMap::new(),
+            classes: HashMap::new(),
+            this: Vec::new(),
+            random: Random::default(),
+            today: None,
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads INPUT from `input` instead of the terminal
+    pub fn with_input(mut self, input: impl InputSource + 'static) -> Self {
+        self.input = Box::new(input);
+        self
+    }
+
+    /// Sends OUTPUT to `output` instead of the terminal
+    pub fn with_output(mut self, output: impl OutputSink + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Opens files in `fs` instead of on disk
+    pub fn with_file_system(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.fs = Box::new(fs);
+        self
+    }
+
+    /// Seeds RAND, so that every run gives the same sequence of numbers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    /// Pins the date NOW returns, instead of reading it from the system clock
+    pub fn with_today(mut self, today: Date) -> Self {
+        self.today = Some(today);
+        self
+    }
+
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    pub fn run(&mut self, program: &[Stmt]) -> Result<(), Error> {
+        self.define(program)?;
+        for stmt in program {
+            self.exec(stmt)?;
+        }
+
+        self.finish()
+    }
+
+    /// Registers the PROCEDUREs and FUNCTIONs at the top level of `program`, and those nested
+    /// in their bodies, ahead of running it so they can be called before the statement defining
+    /// them. Definitions anywhere else are registered by `exec` when it reaches them.
+    pub fn define(&mut self, program: &[Stmt]) -> Result<(), Error> {
+        for stmt in program {
+            let Some((name, subroutine)) = subroutine(stmt) else {
+                continue;
+            };
+
+            if self.subroutines.contains_key(name) {
+                return Err(Error::AlreadyDeclared(name.clone()));
+            }
+
+            self.define(&subroutine.body)?;
+            self.subroutines.insert(name.clone(), Rc::new(subroutine));
+        }
+
+        Ok(())
+    }
+
+    /// Checks a finished program closed every file it opened
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+
+        let mut open = self.files.keys().cloned().collect::<Vec<_>>();
+        open.sort();
+        Err(Error::FilesLeftOpen(open))
+    }
+
+    /// Executes a top level statement, yielding the value of a bare expression. A PROCEDURE or
+    /// FUNCTION is registered here unless `define` has already hoisted it.
+    pub fn exec(&mut self, stmt: &Stmt) -> Result<Option<Value>, Error> {
+        match stmt {
+            Stmt::Expression(Expr::Method(object, method, args)) => {
+                self.call_method(object, method, args)
+            }
+            Stmt::Expression(a) => Ok(Some(self.eval(a)?)),
+            stmt => match self.step(stmt)? {
+                Flow::Continue => Ok(None),
+                Flow::Return(_) => Err(Error::ReturnOutsideFunction),
+            },
+        }
+    }
+
+    /// Calls a built-in or FUNCTION from within an expression
+    fn call_function(&mut self, name: &str, args: &[Expr]) -> Result<Value, Error> {
+        if let Some(builtin) = builtin(name) {
+            let args = args
+                .iter()
+                .map(|arg| self.eval(arg))
+                .collect::<Result<Vec<_>, Error>>()?;
+            return builtin(self, &args);
+        }
+
+        let (function, this) = self
+            .callee(name)?
+            .ok_or_else(|| Error::UndefinedFunction(name.to_owned()))?;
+
+        self.invoke(name, &function, this, args)?
+            .ok_or_else(|| Error::NotAFunction(name.to_owned()))
+    }
+
+    /// Calls a method FUNCTION from within an expression
+    fn call_method_function(
+        &mut self,
+        object: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> Result<Value, Error> {
+        self.call_method(object, method, args)?
+            .ok_or_else(|| Error::NotAFunction(method.to_owned()))
+    }
+
+    /// Value a bare identifier refers to: a variable, or failing that an enum member
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.env
+            .get_value(name)
+            .or_else(|| self.members.get(name).cloned().map(Value::Enum))
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, Error> {
+        let value_error = |e| Error::EvaluationError(EvalError::ValueError(e));
+
+        match expr {
+            Expr::Literal(v) => Ok(v.clone()),
+            Expr::Variable(name) => self.lookup(name).ok_or_else(|| {
+                self.private_attribute(name).unwrap_or_else(|| {
+                    Error::EvaluationError(EvalError::VariableNotFound(name.clone()))
+                })
+            }),
+            Expr::Unary(op, a) => {
+                let a = self.eval(a)?;
+                op.apply_unary(&a).map_err(value_error)
+            }
+            Expr::Binary(a, op, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                op.apply(&a, &b).map_err(value_error)
+            }
+            Expr::Call(name, args) => self.call_function(name, args),
+            Expr::Index(..) | Expr::Field(..) | Expr::Deref(_) => self.select(expr),
+            Expr::AddressOf(a) => self.address_of(a),
+            Expr::New(ty, args) => self.allocate(ty, args),
+            Expr::Method(object, method, args) => self.call_method_function(object, method, args),
+            Expr::Super => Err(Error::NotAnObject(expr.to_string())),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<Flow, Error> {
+        for stmt in stmts {
+            if let Flow::Return(value) = self.step(stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Runs `f` in a scope of its own, so anything it declares is gone afterwards
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        self.env.create_scope();
+        let result = f(self);
+        self.env.end_scope();
+        result
+    }
+
+    /// Resolves an assignable expression to the place it names
+    fn place(&mut self, expr: &Expr) -> Result<Place, Error> {
+        match expr {
+            Expr::Variable(name) => {
+                let place = self.env.resolve(name).cloned().ok_or_else(|| {
+                    self.private_attribute(name)
+                        .unwrap_or_else(|| Error::UndeclaredVariable(name.clone()))
+                })?;
+
+                if self.env.variable(&place.root).constant {
+                    return Err(Error::AssignToConstant(name.clone()));
+                }
+
+                Ok(place)
+            }
+            Expr::Index(base, _) | Expr::Field(base, _) => {
+                let mut place = match self.place(base) {
+                    // Attributes stay assignable however the object was reached
+                    Err(e @ (Error::NotAssignable(_) | Error::AssignToConstant(_))) => {
+                        let object = self.eval(base)?;
+                        return self.object_field(expr, &object)?.ok_or(e);
+                    }
+                    place => place?,
+                };
+
+                if let Some(attribute) = self.object_field(expr, self.env.value(&place))? {
+                    return Ok(attribute);
+                }
+
+                let selector = self.selector(expr)?;
+
+                place
+                    .path
+                    .push(offset(self.env.value(&place), base, &selector)?);
+                Ok(place)
+            }
+            Expr::Deref(pointer) => match self.eval(pointer)? {
+                Value::Pointer(Pointer {
+                    target: Some(address),
+                    ..
+                }) => self
+                    .env
+                    .dereference(&address)
+                    .ok_or_else(|| Error::DanglingPointer(pointer.to_string())),
+                Value::Pointer(_) => Err(Error::NullPointer(pointer.to_string())),
+                _ => Err(Error::NotAPointer(pointer.to_string())),
+            },
+            _ => Err(Error::NotAssignable(expr.to_string())),
+        }
+    }
+
+    /// Declared type of whatever is stored at `place`
+    fn place_type(&self, place: &Place) -> Type {
+        place.path.iter().fold(
+            self.env.variable(&place.root).ty.clone(),
+            |ty, &i| match ty {
+                Type::Array(_, ty) => *ty,
+                Type::Named(ref name) => match self.types.get(name) {
+                    Some(TypeDef::Record(fields)) => fields[i].1.resolve(&self.types),
+                    Some(TypeDef::Class) => {
+                        self.classes[name].attributes[i].ty.resolve(&self.types)
+                    }
+                    _ => ty,
+                },
+                ty => ty,
+            },
+        )
+    }
+
+    fn store(&mut self, place: &Place, value: Value) -> Result<(), Error> {
+        let ty = self.place_type(place);
+        *self.env.value_mut(place) = self.coerce(&ty, value)?;
+        Ok(())
+    }
+
+    /// Checks `value` can be stored as a `ty` like [`Type::coerce`], also letting an
+    /// object stand in for any of its superclasses
+    fn coerce(&self, ty: &Type, value: Value) -> Result<Value, Error> {
+        if let (Type::Named(class), Value::Object(object)) = (ty, &value) {
+            if self.inherits(&object.class, class) {
+                return Ok(value);
+            }
+        }
+
+        let found = value.ty();
+        ty.coerce(value).ok_or_else(|| Error::TypeMismatch {
+            expected: ty.clone(),
+            found,
+        })
+    }
+
+    /// Whether `class` is `ancestor` or inherits from it
+    fn inherits(&self, class: &str, ancestor: &str) -> bool {
+        class == ancestor
+            || self
+                .classes
+                .get(class)
+                .and_then(|info| info.parent.as_deref())
+                .is_some_and(|parent| self.inherits(parent, ancestor))
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), Error> {
+        let place = self.place(target)?;
+        self.store(&place, value)
+    }
+
+    /// Reads an array element or record field, going through its place when it has one
+    /// so the containing value is not copied
+    fn select(&mut self, expr: &Expr) -> Result<Value, Error> {
+        let (Expr::Index(base, _) | Expr::Field(base, _)) = expr else {
+            let place = self.place(expr)?;
+            return Ok(self.env.value(&place).clone());
+        };
+
+        match self.place(base) {
+            Ok(mut place) => {
+                if let Some(attribute) = self.object_field(expr, self.env.value(&place))? {
+                    return Ok(self.env.value(&attribute).clone());
+                }
+
+                let selector = self.selector(expr)?;
+                place
+                    .path
+                    .push(offset(self.env.value(&place), base, &selector)?);
+                Ok(self.env.value(&place).clone())
+            }
+            Err(Error::NotAssignable(_) | Error::AssignToConstant(_)) => {
+                let value = self.eval(base)?;
+                if let Some(attribute) = self.object_field(expr, &value)? {
+                    return Ok(self.env.value(&attribute).clone());
+                }
+
+                let selector = self.selector(expr)?;
+                let i = offset(&value, base, &selector)?;
+                Ok(value.element(i).cloned().expect("offset is in range"))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Pointer to the place `expr` names, as for `@Var`
+    fn address_of(&mut self, expr: &Expr) -> Result<Value, Error> {
+        let place = self.place(expr)?;
+
+        Ok(Value::Pointer(Pointer {
+            ty: self.place_type(&place),
+            target: Some(self.env.address(&place)),
+        }))
+    }
+
+    /// Pointer to a fresh heap cell holding the default value of `ty`, or for a CLASS
+    /// a new object built by its constructor from `args`
+    fn allocate(&mut self, ty: &Type, args: &[Expr]) -> Result<Value, Error> {
+        let ty = ty.resolve(&self.types);
+        if let Type::Named(class) = &ty {
+            if self.classes.contains_key(class) {
+                return self.construct(class, args);
+            }
+        }
+
+        if !args.is_empty() {
+            return Err(Error::ArgumentCount {
+                expected: 0,
+                found: args.len(),
+            });
+        }
+
+        let value = ty
+            .default_value(&self.types)
+            .ok_or_else(|| Error::UndefinedType(ty.to_string()))?;
+        let place = self.env.allocate(Variable::new(ty.clone(), value));
+
+        Ok(Value::Pointer(Pointer {
+            ty,
+            target: Some(self.env.address(&place)),
+        }))
+    }
+
+    fn selector<'a>(&mut self, expr: &'a Expr) -> Result<Selector<'a>, Error> {
+        Ok(match expr {
+            Expr::Index(_, indices) => Selector::Index(self.indices(indices)?),
+            Expr::Field(_, field) => Selector::Field(field),
+            _ => unreachable!(),
+        })
+    }
+
+    fn indices(&mut self, indices: &[Expr]) -> Result<Vec<i64>, Error> {
+        indices.iter().map(|i| self.integer(i)).collect()
+    }
+
+    fn integer(&mut self, expr: &Expr) -> Result<i64, Error> {
+        let value = self.eval(expr)?;
+        value.try_as_integer().map_err(|_| Error::TypeMismatch {
+            expected: Type::Integer,
+            found: value.ty(),
+        })
+    }
+
+    /// The subroutine a bare name calls: a method of the running object, or failing that
+    /// a global PROCEDURE or FUNCTION
+    fn callee(&self, name: &str) -> Result<Option<Callee>, Error> {
+        if let Some(Some(this)) = self.this.last() {
+            if let Some((class, access, method)) = self.find_method(&this.object.class, name) {
+                if access == Access::Private && !self.within(&class) {
+                    return Err(Error::PrivateMember {
+                        class,
+                        member: name.to_owned(),
+                    });
+                }
+
+                let this = This {
+                    object: this.object.clone(),
+                    class,
+                };
+                return Ok(Some((method, Some(this))));
+            }
+        }
+
+        let subroutine = self.subroutines.get(name).cloned();
+        Ok(subroutine.map(|subroutine| (subroutine, None)))
+    }
+
+    /// Calls the method `name` of the object `target` evaluates to, or of the superclass
+    /// for SUPER, yielding its return value if it is a FUNCTION
+    fn call_method(
+        &mut self,
+        target: &Expr,
+        name: &str,
+        args: &[Expr],
+    ) -> Result<Option<Value>, Error> {
+        let (method, this) = self.method(target, name)?;
+        self.invoke(name, &method, Some(this), args)
+    }
+
+    fn method(&mut self, target: &Expr, name: &str) -> Result<(Rc<Subroutine>, This), Error> {
+        let no_such_method = |class: &str| Error::NoSuchMethod {
+            class: class.to_owned(),
+            method: name.to_owned(),
+        };
+
+        if let Expr::Super = target {
+            let Some(Some(this)) = self.this.last() else {
+                return Err(Error::SuperOutsideMethod);
+            };
+            let parent = self.classes[&this.class]
+                .parent
+                .clone()
+                .ok_or_else(|| Error::NoSuperclass(this.class.clone()))?;
+            let (class, access, method) = self
+                .find_method(&parent, name)
+                .ok_or_else(|| no_such_method(&parent))?;
+
+            if access == Access::Private && !self.within(&class) {
+                return Err(Error::PrivateMember {
+                    class,
+                    member: name.to_owned(),
+                });
+            }
+
+            let this = This {
+                object: this.object.clone(),
+                class,
+            };
+            return Ok((method, this));
+        }
+
+        let object = match self.eval(target)? {
+            Value::Object(object) if object.cell.is_none() => {
+                return Err(Error::NullPointer(target.to_string()))
+            }
+            Value::Object(object) => object,
+            _ => return Err(Error::NotAnObject(target.to_string())),
+        };
+        let (class, access, method) = self
+            .find_method(&object.class, name)
+            .ok_or_else(|| no_such_method(&object.class))?;
+
+        if access == Access::Private && !self.within(&class) {
+            return Err(Error::PrivateMember {
+                class,
+                member: name.to_owned(),
+            });
+        }
+
+        Ok((method, This { object, class }))
+    }
+
+    /// The method `name` of `class`, or else the one it inherits, with the class declaring it
+    fn find_method(&self, class: &str, name: &str) -> Option<(String, Access, Rc<Subroutine>)> {
+        let info = self.classes.get(class)?;
+
+        match info.methods.get(name) {
+            Some((access, method)) => Some((class.to_owned(), *access, method.clone())),
+            None => self.find_method(info.parent.as_deref()?, name),
+        }
+    }
+
+    /// Whether the running method was declared by `class`, letting it touch PRIVATE members
+    fn within(&self, class: &str) -> bool {
+        matches!(self.this.last(), Some(Some(this)) if this.class == class)
+    }
+
+    /// The error for a bare `name` that is not in scope because it is a PRIVATE attribute
+    /// of the running object, declared by a class other than the running method's
+    fn private_attribute(&self, name: &str) -> Option<Error> {
+        let Some(Some(this)) = self.this.last() else {
+            return None;
+        };
+
+        self.classes[&this.object.class]
+            .attributes
+            .iter()
+            .rfind(|attribute| attribute.name == name && attribute.access == Access::Private)
+            .map(|attribute| Error::PrivateMember {
+                class: attribute.owner.clone(),
+                member: name.to_owned(),
+            })
+    }
+
+    /// Place of the attribute `expr` names, if it is a field access on the object `base`
+    fn object_field(&self, expr: &Expr, base: &Value) -> Result<Option<Place>, Error> {
+        let (Expr::Field(target, field), Value::Object(object)) = (expr, base) else {
+            return Ok(None);
+        };
+
+        let cell = object
+            .cell
+            .ok_or_else(|| Error::NullPointer(target.to_string()))?;
+        let attributes = &self.classes[&object.class].attributes;
+        let i = attributes
+            .iter()
+            .rposition(|attribute| attribute.name == *field)
+            .ok_or_else(|| Error::NoSuchField {
+                record: object.class.clone(),
+                field: field.clone(),
+            })?;
+
+        let attribute = &attributes[i];
+        if attribute.access == Access::Private && !self.within(&attribute.owner) {
+            return Err(Error::PrivateMember {
+                class: attribute.owner.clone(),
+                member: field.clone(),
+            });
+        }
+
+        Ok(Some(Place {
+            root: Root::Heap(cell),
+            path: vec![i],
+        }))
+    }
+
+    /// Allocates an object of `class` and runs its constructor, PROCEDURE NEW, on it
+    fn construct(&mut self, class: &str, args: &[Expr]) -> Result<Value, Error> {
+        let fields = self.classes[class]
+            .attributes
+            .iter()
+            .map(|attribute| {
+                let ty = attribute.ty.resolve(&self.types);
+                let value = ty
+                    .default_value(&self.types)
+                    .ok_or_else(|| Error::UndefinedType(ty.to_string()))?;
+                Ok((attribute.name.clone(), value))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let record = Record {
+            ty: class.to_owned(),
+            fields,
+        };
+        let place = self.env.allocate(Variable::new(
+            Type::Named(class.to_owned()),
+            Value::Record(record),
+        ));
+        let Root::Heap(cell) = place.root else {
+            unreachable!("allocated places live on the heap")
+        };
+        let object = Object {
+            class: class.to_owned(),
+            cell: Some(cell),
+        };
+
+        match self.find_method(class, "NEW") {
+            Some((owner, _, constructor)) => {
+                let this = This {
+                    object: object.clone(),
+                    class: owner,
+                };
+                self.invoke("NEW", &constructor, Some(this), args)?;
+            }
+            None if !args.is_empty() => {
+                return Err(Error::ArgumentCount {
+                    expected: 0,
+                    found: args.len(),
+                })
+            }
+            None => {}
+        }
+
+        Ok(Value::Object(object))
+    }
+
+    /// Runs a subroutine in a fresh call frame, yielding its return value if it is a FUNCTION.
+    /// A method also sees the attributes of `this` it has access to, which its parameters
+    /// and locals shadow.
+    fn invoke(
+        &mut self,
+        name: &str,
+        subroutine: &Subroutine,
+        this: Option<This>,
+        args: &[Expr],
+    ) -> Result<Option<Value>, Error> {
+        let params = &subroutine
+            .params
+            .iter()
+            .map(|param| Param {
+                ty: param.ty.resolve(&self.types),
+                ..param.clone()
+            })
+            .collect::<Vec<_>>();
+
+        if params.len() != args.len() {
+            return Err(Error::ArgumentCount {
+                expected: params.len(),
+                found: args.len(),
+            });
+        }
+
+        // Arguments are resolved in the caller's scope before the frame is opened
+        let mut arguments = Vec::with_capacity(args.len());
+        for (param, arg) in params.iter().zip(args) {
+            arguments.push(match param.mode {
+                PassBy::Value => {
+                    let value = self.eval(arg)?;
+                    Argument::Value(self.coerce(&param.ty, value)?)
+                }
+                PassBy::Reference => {
+                    let place = match self.place(arg) {
+                        Err(Error::NotAssignable(_)) => {
+                            return Err(Error::NotAReference(param.name.clone()))
+                        }
+                        place => place?,
+                    };
+
+                    let found = self.place_type(&place);
+                    if found != param.ty {
+                        return Err(Error::TypeMismatch {
+                            expected: param.ty.clone(),
+                            found,
+                        });
+                    }
+                    Argument::Reference(place)
+                }
+            });
+        }
+
+        self.env.create_frame();
+        if let Some(this) = &this {
+            self.bind_attributes(this);
+        }
+        self.env.create_scope();
+        self.this.push(this);
+        let flow = self
+            .bind_arguments(params, arguments)
+            .and_then(|_| self.block(&subroutine.body));
+        self.this.pop();
+        self.env.end_scope();
+        self.env.end_scope();
+
+        match (flow?, &subroutine.returns) {
+            (Flow::Continue, None) => Ok(None),
+            (Flow::Return(value), Some(ty)) => {
+                let ty = ty.resolve(&self.types);
+                self.coerce(&ty, value).map(Some)
+            }
+            (Flow::Continue, Some(_)) => Err(Error::MissingReturn(name.to_owned())),
+            (Flow::Return(_), None) => Err(Error::ReturnOutsideFunction),
+        }
+    }
+
+    /// Binds the attributes of `this.object` the running method may use to their names.
+    /// An attribute redeclared by a subclass hides the one it inherits.
+    fn bind_attributes(&mut self, this: &This) {
+        let cell = this
+            .object
+            .cell
+            .expect("methods are only called on objects");
+
+        for (i, attribute) in self.classes[&this.object.class]
+            .attributes
+            .iter()
+            .enumerate()
+            .rev()
+        {
+            if attribute.access == Access::Public || attribute.owner == this.class {
+                let place = Place {
+                    root: Root::Heap(cell),
+                    path: vec![i],
+                };
+                self.env.bind(&attribute.name, place);
+            }
+        }
+    }
+
+    /// Registers a CLASS as a type, laying out its attributes after those it inherits
+    fn define_class(&mut self, name: &str, class: &Class) -> Result<(), Error> {
+        let mut attributes = match &class.parent {
+            Some(parent) => self
+                .classes
+                .get(parent)
+                .ok_or_else(|| Error::UndefinedType(parent.clone()))?
+                .attributes
+                .clone(),
+            None => Vec::new(),
+        };
+
+        attributes.extend(
+            class
+                .attributes
+                .iter()
+                .map(|(access, attribute, ty)| Attribute {
+                    name: attribute.clone(),
+                    ty: ty.clone(),
+                    access: *access,
+                    owner: name.to_owned(),
+                }),
+        );
+
+        let methods = class
+            .methods
+            .iter()
+            .map(|method| {
+                let subroutine = Subroutine {
+                    params: method.params.clone(),
+                    returns: method.returns.clone(),
+                    body: method.body.clone(),
+                };
+                (method.name.clone(), (method.access, Rc::new(subroutine)))
+            })
+            .collect();
+
+        let info = ClassInfo {
+            parent: class.parent.clone(),
+            attributes,
+            methods,
+        };
+        self.classes.insert(name.to_owned(), info);
+        self.types.insert(name.to_owned(), TypeDef::Class);
+        Ok(())
+    }
+
+    fn bind_arguments(&mut self, params: &[Param], arguments: Vec<Argument>) -> Result<(), Error> {
+        for (param, argument) in params.iter().zip(arguments) {
+            let bound = match argument {
+                Argument::Reference(place) => self.env.bind(&param.name, place),
+                Argument::Value(value) => self
+                    .env
+                    .declare(&param.name, Variable::new(param.ty.clone(), value)),
+            };
+
+            if !bound {
+                return Err(Error::AlreadyDeclared(param.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registers the members of an enumerated TYPE, which may not reuse a name
+    /// taken by any other member
+    fn define_members(&mut self, ty: &str, members: &[String]) -> Result<(), Error> {
+        for (ordinal, member) in members.iter().enumerate() {
+            let clash = self
+                .members
+                .get(member)
+                .map(|e| e.ty.clone())
+                .or_else(|| members[..ordinal].contains(member).then(|| ty.to_owned()));
+
+            if let Some(first) = clash {
+                return Err(Error::DuplicateEnumMember {
+                    member: member.clone(),
+                    first,
+                    second: ty.to_owned(),
+                });
+            }
+        }
+
+        for (ordinal, member) in members.iter().enumerate() {
+            let value = Enum {
+                ty: ty.to_owned(),
+                member: member.clone(),
+                ordinal,
+            };
+            self.members.insert(member.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    /// Whether `ty` leads back to the type called `name` through pointer and array types alone,
+    /// which would make a pointer TYPE named `name` infinitely deep
+    fn refers_to(&self, ty: &Type, name: &str) -> bool {
+        match ty {
+            Type::Array(_, ty) | Type::Pointer(ty) => self.refers_to(ty, name),
+            Type::Named(n) if n == name => true,
+            Type::Named(n) => match self.types.get(n) {
+                Some(TypeDef::Pointer(ty)) => self.refers_to(ty, name),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a value of `ty` holds a value of the type called `name` directly, through
+    /// arrays or through the fields of records, which would make a record TYPE named `name`
+    /// infinitely large. Pointers and objects break the chain as they only refer to a value.
+    fn contains(&self, ty: &Type, name: &str) -> bool {
+        match ty {
+            Type::Array(_, ty) => self.contains(ty, name),
+            Type::Named(n) if n == name => true,
+            Type::Named(n) => match self.types.get(n) {
+                Some(TypeDef::Record(fields)) => {
+                    fields.iter().any(|(_, ty)| self.contains(ty, name))
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Member of the enumerated TYPE `ty` at `ordinal`
+    fn member(&self, ty: &str, ordinal: usize) -> Value {
+        match self.types.get(ty) {
+            Some(TypeDef::Enum(members)) => Value::Enum(Enum {
+                ty: ty.to_owned(),
+                member: members[ordinal].clone(),
+                ordinal,
+            }),
+            _ => unreachable!("enum values only exist for defined enum types"),
+        }
+    }
+
+    fn file_name(&mut self, expr: &Expr) -> Result<String, Error> {
+        let value = self.eval(expr)?;
+        value.try_as_string().map_err(|_| Error::TypeMismatch {
+            expected: Type::String,
+            found: value.ty(),
+        })
+    }
+
+    /// The file `name`, which must be open in one of `modes`
+    fn open_file(&mut self, name: &str, modes: &[FileMode]) -> Result<&mut OpenFile, Error> {
+        let file = self
+            .files
+            .get_mut(name)
+            .ok_or_else(|| Error::FileNotOpen(name.to_owned()))?;
+
+        if !modes.contains(&file.mode) {
+            return Err(Error::WrongFileMode {
+                file: name.to_owned(),
+                mode: file.mode,
+            });
+        }
+
+        Ok(file)
+    }
+
+    pub(crate) fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+
+    /// The current date, as of UTC unless the host has pinned it
+    pub(crate) fn today(&self) -> Date {
+        self.today
+            .unwrap_or_else(|| OffsetDateTime::now_utc().date())
+    }
+
+    /// Whether every line of the file `name`, open for READ, has been read
+    pub(crate) fn eof(&mut self, name: &str) -> Result<bool, Error> {
+        let file = self.open_file(name, &[FileMode::Read])?;
+        Ok(file.lines.is_empty())
+    }
+
+    fn open(&mut self, name: String, mode: FileMode) -> Result<(), Error> {
+        if self.files.contains_key(&name) {
+            return Err(Error::FileAlreadyOpen(name));
+        }
+
+        let file_error = |error| Error::FileError {
+            file: name.clone(),
+            error,
+        };
+
+        let mut lines = VecDeque::new();
+        match mode {
+            FileMode::Read => {
+                let contents = String::from_utf8(self.fs.read(&name).map_err(file_error)?)
+                    .map_err(|e| file_error(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+                lines = contents.lines().map(str::to_owned).collect();
+            }
+            FileMode::Write => self.fs.write(&name, &[]).map_err(file_error)?,
+            FileMode::Append | FileMode::Random => {
+                self.fs.append(&name, &[]).map_err(file_error)?
+            }
+        }
+
+        let file = OpenFile {
+            mode,
+            lines,
+            position: 0,
+        };
+        self.files.insert(name, file);
+        Ok(())
+    }
+
+    /// Size of a record of type `ty`, and the offset of the record at the current
+    /// position of the RANDOM file `name`
+    fn record(&mut self, name: &str, ty: &Type) -> Result<(usize, usize), Error> {
+        let size =
+            files::record_size(ty, &self.types).ok_or_else(|| Error::NotStorable(ty.clone()))?;
+        let file = self.open_file(name, &[FileMode::Random])?;
+
+        // Records past the largest offset a file can hold have no address
+        let offset = file
+            .position
+            .checked_mul(size)
+            .filter(|offset| offset.checked_add(size).is_some())
+            .ok_or(Error::InvalidAddress(file.position as i64 + 1))?;
+        Ok((size, offset))
+    }
+
+    fn get_record(&mut self, name: String, target: &Expr) -> Result<(), Error> {
+        let place = self.place(target)?;
+        let ty = self.place_type(&place);
+        let (size, offset) = self.record(&name, &ty)?;
+
+        let contents = self
+            .fs
+            .read_at(&name, offset, size)
+            .map_err(|error| Error::FileError {
+                file: name.clone(),
+                error,
+            })?;
+        if contents.len() < size {
+            return Err(Error::EndOfFile(name));
+        }
+        let value = files::decode(&ty, &self.types, &mut contents.as_slice())
+            .ok_or_else(|| Error::CorruptRecord(name.clone()))?;
+
+        self.store(&place, value)?;
+        self.open_file(&name, &[FileMode::Random])?.position += 1;
+        Ok(())
+    }
+
+    fn put_record(&mut self, name: String, record: &Expr) -> Result<(), Error> {
+        let value = self.eval(record)?;
+        let ty = value.ty();
+        let (size, offset) = self.record(&name, &ty)?;
+
+        let mut bytes = Vec::with_capacity(size);
+        files::encode(&value, &mut bytes).ok_or(Error::NotStorable(ty))?;
+
+        self.fs
+            .write_at(&name, offset, &bytes)
+            .map_err(|error| Error::FileError {
+                file: name.clone(),
+                error,
+            })?;
+        self.open_file(&name, &[FileMode::Random])?.position += 1;
+        Ok(())
+    }
+
+    /// Evaluates a CASE label into the inclusive range of values it matches
+    fn case_range(&mut self, label: &CaseLabel) -> Result<(Value, Value), Error> {
+        Ok(match label {
+            CaseLabel::Value(v) => {
+                let v = self.eval(v)?;
+                (v.clone(), v)
+            }
+            CaseLabel::Range(lo, hi) => (self.eval(lo)?, self.eval(hi)?),
+        })
+    }
+
+    fn condition(&mut self, expr: &Expr) -> Result<bool, Error> {
+        let value = self.eval(expr)?;
+        value.try_as_boolean().map_err(|_| Error::TypeMismatch {
+            expected: Type::Boolean,
+            found: value.ty(),
+        })
+    }
+
+    fn step(&mut self, stmt: &Stmt) -> Result<Flow, Error> {
+        match stmt {
+            Stmt::Expression(Expr::Method(object, method, args)) => {
+                self.call_method(object, method, args)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Expression(a) => {
+                self.eval(a)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Declare(name, ty) => {
+                let ty = &ty.resolve(&self.types);
+                let value = ty
+                    .default_value(&self.types)
+                    .ok_or_else(|| Error::UndefinedType(ty.to_string()))?;
+
+                if !self.env.declare(name, Variable::new(ty.clone(), value)) {
+                    return Err(Error::AlreadyDeclared(name.clone()));
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::Constant(name, value) => {
+                let value = self.eval(value)?;
+                if !self.env.declare(name, Variable::constant(value)) {
+                    return Err(Error::AlreadyDeclared(name.clone()));
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::TypeDef(name, def) => {
+                if self.types.contains_key(name) {
+                    return Err(Error::AlreadyDeclared(name.clone()));
+                }
+
+                match def {
+                    TypeDef::Enum(members) => self.define_members(name, members)?,
+                    TypeDef::Pointer(ty) if self.refers_to(ty, name) => {
+                        return Err(Error::CyclicType(name.clone()))
+                    }
+                    TypeDef::Record(fields)
+                        if fields.iter().any(|(_, ty)| self.contains(ty, name)) =>
+                    {
+                        return Err(Error::CyclicType(name.clone()))
+                    }
+                    _ => {}
+                }
+
+                self.types.insert(name.clone(), def.clone());
+                Ok(Flow::Continue)
+            }
+            Stmt::Define(name, elements, ty) => {
+                let base = match self.types.get(ty) {
+                    Some(TypeDef::Set(base)) => base.clone(),
+                    Some(_) => return Err(Error::NotASet(ty.clone())),
+                    None => return Err(Error::UndefinedType(ty.clone())),
+                };
+
+                let mut set = Set::new(ty.clone(), base.clone());
+                for element in elements {
+                    let value = self.eval(element)?;
+                    let found = value.ty();
+                    set.insert(base.coerce(value).ok_or(Error::TypeMismatch {
+                        expected: base.clone(),
+                        found,
+                    })?);
+                }
+
+                let var = Variable::new(Type::Named(ty.clone()), Value::Set(set));
+                if !self.env.declare(name, var) {
+                    return Err(Error::AlreadyDeclared(name.clone()));
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::Assign(target, expr) => {
+                let value = self.eval(expr)?;
+                self.assign(target, value)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Output(vs) => {
+                let line = vs
+                    .iter()
+                    .map(|expr| self.eval(expr).map(|v| v.to_string()))
                     .collect::<Result<Vec<String>, Error>>()?
-                    .join(" ")
-            );
-            Ok(None)
+                    .join(" ");
+
+                self.output.write_line(&line);
+                Ok(Flow::Continue)
+            }
+            Stmt::Input(target) => {
+                let place = self.place(target)?;
+                let ty = self.place_type(&place);
+                let line = self.input.read_line().ok_or(Error::EndOfInput)?;
+                let value = input_value(&line, &ty).ok_or(Error::InvalidInput {
+                    expected: ty,
+                    input: line,
+                })?;
+
+                self.store(&place, value)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::OpenFile(file, mode) => {
+                let name = self.file_name(file)?;
+                self.open(name, *mode)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::ReadFile(file, target) => {
+                let name = self.file_name(file)?;
+                let line = self
+                    .open_file(&name, &[FileMode::Read])?
+                    .lines
+                    .pop_front()
+                    .ok_or(Error::EndOfFile(name))?;
+
+                self.assign(target, Value::String(line))?;
+                Ok(Flow::Continue)
+            }
+            Stmt::WriteFile(file, data) => {
+                let name = self.file_name(file)?;
+                let line = format!("{}\n", self.eval(data)?);
+                self.open_file(&name, &[FileMode::Write, FileMode::Append])?;
+
+                self.fs
+                    .append(&name, line.as_bytes())
+                    .map_err(|error| Error::FileError { file: name, error })?;
+                Ok(Flow::Continue)
+            }
+            Stmt::CloseFile(file) => {
+                let name = self.file_name(file)?;
+                self.files.remove(&name).ok_or(Error::FileNotOpen(name))?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Seek(file, address) => {
+                let name = self.file_name(file)?;
+                let address = self.integer(address)?;
+                let file = self.open_file(&name, &[FileMode::Random])?;
+
+                if address < 1 {
+                    return Err(Error::InvalidAddress(address));
+                }
+
+                file.position = (address - 1) as usize;
+                Ok(Flow::Continue)
+            }
+            Stmt::GetRecord(file, target) => {
+                let name = self.file_name(file)?;
+                self.get_record(name, target)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::PutRecord(file, record) => {
+                let name = self.file_name(file)?;
+                self.put_record(name, record)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::If(cond, then, otherwise) => {
+                if self.condition(cond)? {
+                    self.block(then)
+                } else if let Some(otherwise) = otherwise {
+                    self.block(otherwise)
+                } else {
+                    Ok(Flow::Continue)
+                }
+            }
+            Stmt::For(var, from, to, step, body) => {
+                // Bounds and step are fixed before the first iteration
+                let from = self.eval(from)?;
+                let to = self.eval(to)?;
+                let step = match step {
+                    Some(step) => self.integer(step)?,
+                    None => 1,
+                };
+
+                if step == 0 {
+                    return Err(Error::ZeroStep);
+                }
+
+                // Enum ranges step through the members by ordinal
+                let (mut i, end) = match (&from, &to) {
+                    (Value::Integer(a), Value::Integer(b)) => (*a, *b),
+                    (Value::Enum(a), Value::Enum(b)) if a.ty == b.ty => {
+                        (a.ordinal as i64, b.ordinal as i64)
+                    }
+                    (Value::Integer(_) | Value::Enum(_), _) => {
+                        return Err(Error::TypeMismatch {
+                            expected: from.ty(),
+                            found: to.ty(),
+                        })
+                    }
+                    _ => {
+                        return Err(Error::TypeMismatch {
+                            expected: Type::Integer,
+                            found: from.ty(),
+                        })
+                    }
+                };
+
+                let counter = self.place(&Expr::Variable(var.clone()))?;
+                while (step > 0 && i <= end) || (step < 0 && i >= end) {
+                    let value = match &from {
+                        Value::Enum(e) => self.member(&e.ty, i as usize),
+                        _ => Value::Integer(i),
+                    };
+                    self.store(&counter, value)?;
+                    if let Flow::Return(value) = self.scoped(|this| this.block(body))? {
+                        return Ok(Flow::Return(value));
+                    }
+
+                    // Stepping past the largest INTEGER also takes the counter past `end`
+                    match i.checked_add(step) {
+                        Some(next) => i = next,
+                        None => break,
+                    }
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::While(cond, body) => {
+                while self.condition(cond)? {
+                    if let Flow::Return(value) = self.scoped(|this| this.block(body))? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::Repeat(body, cond) => {
+                loop {
+                    // The condition can see what the body declared
+                    let flow = self.scoped(|this| match this.block(body)? {
+                        Flow::Continue => this.condition(cond).map(|done| (Flow::Continue, done)),
+                        flow => Ok((flow, true)),
+                    })?;
+
+                    match flow {
+                        (Flow::Return(value), _) => return Ok(Flow::Return(value)),
+                        (Flow::Continue, true) => break,
+                        (Flow::Continue, false) => {}
+                    }
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::Procedure(..) | Stmt::Function(..) => {
+                // Already registered if `define` hoisted it, or this statement ran before
+                if let Some((name, subroutine)) = subroutine(stmt) {
+                    self.subroutines
+                        .entry(name.clone())
+                        .or_insert_with(|| Rc::new(subroutine));
+                }
+
+                Ok(Flow::Continue)
+            }
+            Stmt::Call(name, args) => match self.callee(name)? {
+                None => Err(Error::UndefinedProcedure(name.clone())),
+                Some((subroutine, _)) if subroutine.returns.is_some() => {
+                    Err(Error::NotAProcedure(name.clone()))
+                }
+                Some((subroutine, this)) => self
+                    .invoke(name, &subroutine, this, args)
+                    .map(|_| Flow::Continue),
+            },
+            Stmt::CallMethod(object, method, args) => {
+                let (subroutine, this) = self.method(object, method)?;
+                if subroutine.returns.is_some() {
+                    return Err(Error::NotAProcedure(method.clone()));
+                }
+
+                self.invoke(method, &subroutine, Some(this), args)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Class(name, class) => {
+                if self.types.contains_key(name) {
+                    return Err(Error::AlreadyDeclared(name.clone()));
+                }
+
+                self.define_class(name, class)?;
+                Ok(Flow::Continue)
+            }
+            Stmt::Return(expr) => Ok(Flow::Return(self.eval(expr)?)),
+            Stmt::Case(subject, clauses, otherwise) => {
+                let subject = self.eval(subject)?;
+                let ranges = clauses
+                    .iter()
+                    .map(|(label, _)| self.case_range(label))
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                // The parser has already compared labels that fold to constants
+                for (i, (label, _)) in clauses.iter().enumerate() {
+                    if label.fold().is_some() {
+                        continue;
+                    }
+
+                    for (j, other) in ranges.iter().enumerate() {
+                        if j != i
+                            && CaseLabel::overlap(&ranges[i], other).map_err(Error::ValueError)?
+                        {
+                            let (first, second) = if j < i {
+                                (other, &ranges[i])
+                            } else {
+                                (&ranges[i], other)
+                            };
+                            return Err(Error::OverlappingCaseLabels(
+                                format!("{} TO {}", first.0, first.1),
+                                format!("{} TO {}", second.0, second.1),
+                            ));
+                        }
+                    }
+                }
+
+                for ((lo, hi), (_, body)) in ranges.iter().zip(clauses) {
+                    if test(subject.ge(lo))? && test(subject.le(hi))? {
+                        return self.block(body);
+                    }
+                }
+
+                match otherwise {
+                    Some(otherwise) => self.block(otherwise),
+                    None => Ok(Flow::Continue),
+                }
+            }
         }
-        Stmt::Input(_) => todo!(),
+    }
+}
+
+/// Position within `value`, the value of `base`, of the component `selector` picks out
+fn offset(value: &Value, base: &Expr, selector: &Selector) -> Result<usize, Error> {
+    match (value, selector) {
+        (Value::Array(array), Selector::Index(indices)) => element_offset(array, indices, base),
+        (Value::Record(record), Selector::Field(field)) => {
+            record.field(field).ok_or_else(|| Error::NoSuchField {
+                record: record.ty.clone(),
+                field: field.to_string(),
+            })
+        }
+        (_, Selector::Index(_)) => Err(Error::NotAnArray(base.to_string())),
+        (_, Selector::Field(_)) => Err(Error::NotARecord(base.to_string())),
+    }
+}
+
+/// Position of the element at `indices`, checking each against the array's bounds
+fn element_offset(array: &Array, indices: &[i64], name: &Expr) -> Result<usize, Error> {
+    if indices.len() != array.bounds.len() {
+        return Err(Error::IndexCount {
+            array: name.to_string(),
+            expected: array.bounds.len(),
+            found: indices.len(),
+        });
+    }
+
+    for (&index, &bounds) in indices.iter().zip(&array.bounds) {
+        if index < bounds.0 || index > bounds.1 {
+            return Err(Error::IndexOutOfBounds {
+                array: name.to_string(),
+                index,
+                bounds,
+            });
+        }
+    }
+
+    Ok(array.offset(indices))
+}
+
+/// Unwraps the BOOLEAN result of a comparison
+fn test(result: Result<Value, ValueError>) -> Result<bool, Error> {
+    result
+        .and_then(|v| v.try_as_boolean())
+        .map_err(Error::ValueError)
+}
+
+/// The name and subroutine a PROCEDURE or FUNCTION statement defines
+fn subroutine(stmt: &Stmt) -> Option<(&String, Subroutine)> {
+    match stmt {
+        Stmt::Procedure(name, params, body) => Some((
+            name,
+            Subroutine {
+                params: params.clone(),
+                returns: None,
+                body: body.clone(),
+            },
+        )),
+        Stmt::Function(name, params, returns, body) => Some((
+            name,
+            Subroutine {
+                params: params.clone(),
+                returns: Some(returns.clone()),
+                body: body.clone(),
+            },
+        )),
+        _ => None,
     }
 }
@@ -0,0 +1,264 @@
+use crate::common::{Array, Enum, Record, Type, TypeDef, Value};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Debug,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    rc::Rc,
+};
+use time::Date;
+
+/// Storage behind OPENFILE, with files named by the strings programs open them by
+pub trait FileSystem: Debug {
+    /// Whole contents of the file at `path`
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Replaces the contents of the file at `path`, creating it if needed
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()>;
+
+    /// Adds `contents` to the end of the file at `path`, creating it if needed
+    fn append(&mut self, path: &str, contents: &[u8]) -> io::Result<()>;
+
+    /// Up to `len` bytes of the file at `path` from byte `offset`, fewer if the file
+    /// ends first
+    fn read_at(&self, path: &str, offset: usize, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Overwrites the file at `path` with `contents` from byte `offset`, creating it
+    /// if needed and filling any gap past its end with zeros
+    fn write_at(&mut self, path: &str, offset: usize, contents: &[u8]) -> io::Result<()>;
+}
+
+/// Files on disk, with paths relative to the working directory
+#[derive(Debug, Default)]
+pub struct DiskFileSystem;
+
+impl FileSystem for DiskFileSystem {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn append(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(contents)
+    }
+
+    fn read_at(&self, path: &str, offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        let mut contents = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn write_at(&mut self, path: &str, offset: usize, contents: &[u8]) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.write_all(contents)
+    }
+}
+
+/// Files held in memory. Clones share the same files, so the host can prepare
+/// inputs and inspect outputs through one clone while the interpreter uses another.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    files: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, path: &str, contents: impl Into<Vec<u8>>) {
+        self.files
+            .borrow_mut()
+            .insert(path.to_owned(), contents.into());
+    }
+
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.borrow().get(path).cloned()
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.contents(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path))
+    }
+
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn append(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        self.files
+            .borrow_mut()
+            .entry(path.to_owned())
+            .or_default()
+            .extend_from_slice(contents);
+        Ok(())
+    }
+
+    fn read_at(&self, path: &str, offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        let files = self.files.borrow();
+        let contents = files
+            .get(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path))?;
+
+        let start = offset.min(contents.len());
+        let end = offset.saturating_add(len).min(contents.len());
+        Ok(contents[start..end].to_vec())
+    }
+
+    fn write_at(&mut self, path: &str, offset: usize, contents: &[u8]) -> io::Result<()> {
+        let mut files = self.files.borrow_mut();
+        let file = files.entry(path.to_owned()).or_default();
+
+        let end = offset
+            .checked_add(contents.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        if file.len() < end {
+            // A far off offset fails here rather than aborting on allocation
+            file.try_reserve(end - file.len())
+                .map_err(|error| io::Error::new(io::ErrorKind::OutOfMemory, error))?;
+            file.resize(end, 0);
+        }
+
+        file[offset..end].copy_from_slice(contents);
+        Ok(())
+    }
+}
+
+/// Bytes a STRING takes up in a random file: a two byte length and up to this many
+/// bytes of text
+pub const STRING_CAPACITY: usize = 254;
+
+/// Size in bytes of a value of type `ty` in a random file, or `None` if the type
+/// has no fixed-size layout. See [`encode`] for the layout itself.
+pub fn record_size(ty: &Type, defs: &HashMap<String, TypeDef>) -> Option<usize> {
+    Some(match ty {
+        Type::Integer | Type::Real => 8,
+        Type::Boolean => 1,
+        Type::Char | Type::Date => 4,
+        Type::String => 2 + STRING_CAPACITY,
+        Type::Array(bounds, ty) => Array::len(bounds)?.checked_mul(record_size(ty, defs)?)?,
+        Type::Named(name) => match defs.get(name)? {
+            TypeDef::Record(fields) => fields.iter().try_fold(0usize, |size, (_, ty)| {
+                size.checked_add(record_size(ty, defs)?)
+            })?,
+            TypeDef::Enum(_) => 4,
+            TypeDef::Pointer(_) | TypeDef::Set(_) | TypeDef::Class => return None,
+        },
+        Type::ArrayOf(..) | Type::Pointer(_) => return None,
+    })
+}
+
+/// Appends the bytes of `value` as stored in a random file to `out`, failing for
+/// values without a layout and strings too long to fit.
+///
+/// A random file is a plain sequence of records with no header, record `n`
+/// (counting from 1) starting at byte `(n - 1) * size`. Gaps left by writing past
+/// the end are filled with zeros. Within a record, all numbers are little-endian:
+///
+/// | Type       | Bytes | Layout                                                   |
+/// |------------|-------|----------------------------------------------------------|
+/// | INTEGER    | 8     | two's complement                                         |
+/// | REAL       | 8     | IEEE 754 double                                          |
+/// | BOOLEAN    | 1     | 0 for FALSE, 1 for TRUE                                  |
+/// | CHAR       | 4     | Unicode scalar value                                     |
+/// | STRING     | 256   | length in bytes (2 bytes), UTF-8 text padded with zeros |
+/// | DATE       | 4     | Julian day number                                        |
+/// | enum       | 4     | ordinal of the member                                    |
+/// | ARRAY      |       | each element in turn, in row-major order                 |
+/// | record     |       | each field in turn, in declaration order                 |
+pub fn encode(value: &Value, out: &mut Vec<u8>) -> Option<()> {
+    match value {
+        Value::Integer(i) => out.extend(i.to_le_bytes()),
+        Value::Real(r) => out.extend(r.to_le_bytes()),
+        Value::Boolean(b) => out.push(*b as u8),
+        Value::Character(c) => out.extend((*c as u32).to_le_bytes()),
+        Value::String(s) => {
+            if s.len() > STRING_CAPACITY {
+                return None;
+            }
+
+            out.extend((s.len() as u16).to_le_bytes());
+            out.extend(s.as_bytes());
+            out.resize(out.len() + STRING_CAPACITY - s.len(), 0);
+        }
+        Value::Date(d) => out.extend(d.to_julian_day().to_le_bytes()),
+        Value::Enum(e) => out.extend((e.ordinal as u32).to_le_bytes()),
+        Value::Array(a) => a.elements.iter().try_for_each(|v| encode(v, out))?,
+        Value::Record(r) => r.fields.iter().try_for_each(|(_, v)| encode(v, out))?,
+        Value::Pointer(_) | Value::Set(_) | Value::Object(_) => return None,
+    }
+
+    Some(())
+}
+
+/// Reads a value of type `ty` from the front of `bytes`, advancing past it.
+/// `None` if the bytes do not hold a valid value of that type.
+pub fn decode(ty: &Type, defs: &HashMap<String, TypeDef>, bytes: &mut &[u8]) -> Option<Value> {
+    let mut take = |n: usize| {
+        let (head, rest) = bytes.split_at_checked(n)?;
+        *bytes = rest;
+        Some(head)
+    };
+
+    Some(match ty {
+        Type::Integer => Value::Integer(i64::from_le_bytes(take(8)?.try_into().ok()?)),
+        Type::Real => Value::Real(f64::from_le_bytes(take(8)?.try_into().ok()?)),
+        Type::Boolean => Value::Boolean(take(1)?[0] != 0),
+        Type::Char => Value::Character(char::from_u32(u32::from_le_bytes(
+            take(4)?.try_into().ok()?,
+        ))?),
+        Type::String => {
+            let len = u16::from_le_bytes(take(2)?.try_into().ok()?) as usize;
+            let text = take(STRING_CAPACITY)?.get(..len)?;
+            Value::String(String::from_utf8(text.to_vec()).ok()?)
+        }
+        Type::Date => {
+            Value::Date(Date::from_julian_day(i32::from_le_bytes(take(4)?.try_into().ok()?)).ok()?)
+        }
+        Type::Array(bounds, elem) => {
+            let mut array = Array::new(bounds.clone(), *elem.clone(), Value::Boolean(false));
+            for element in array.elements.iter_mut() {
+                *element = decode(elem, defs, bytes)?;
+            }
+            Value::Array(array)
+        }
+        Type::Named(name) => match defs.get(name)? {
+            TypeDef::Record(fields) => Value::Record(Record {
+                ty: name.clone(),
+                fields: fields
+                    .iter()
+                    .map(|(field, ty)| Some((field.clone(), decode(ty, defs, bytes)?)))
+                    .collect::<Option<_>>()?,
+            }),
+            TypeDef::Enum(members) => {
+                let ordinal = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+                Value::Enum(Enum {
+                    ty: name.clone(),
+                    member: members.get(ordinal)?.clone(),
+                    ordinal,
+                })
+            }
+            TypeDef::Pointer(_) | TypeDef::Set(_) | TypeDef::Class => return None,
+        },
+        Type::ArrayOf(..) | Type::Pointer(_) => return None,
+    })
+}
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Debug, io, rc::Rc};
+
+/// Where INPUT statements read their lines from
+pub trait InputSource: Debug {
+    /// Next line of input without its line ending, or `None` once input runs out
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// Reads lines typed at the terminal
+#[derive(Debug, Default)]
+pub struct StdinInput;
+
+impl InputSource for StdinInput {
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_owned()),
+        }
+    }
+}
+
+/// Replays a fixed list of lines, as for tests and graders
+#[derive(Debug, Default)]
+pub struct ScriptedInput {
+    lines: VecDeque<String>,
+}
+
+impl ScriptedInput {
+    pub fn new<S: Into<String>>(lines: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            lines: lines.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn read_line(&mut self) -> Option<String> {
+        self.lines.pop_front()
+    }
+}
+
+/// Where OUTPUT statements send their lines
+pub trait OutputSink: Debug {
+    fn write_line(&mut self, line: &str);
+}
+
+/// Prints lines to the terminal
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// Collects lines in memory. Clones share the same buffer, so the host can keep one
+/// to read back what a program printed after handing the other to the interpreter.
+#[derive(Debug, Clone, Default)]
+pub struct BufferSink {
+    lines: Rc<RefCell<Vec<String>>>,
+}
+
+impl BufferSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.borrow().clone()
+    }
+
+    /// Everything written so far, each line followed by a newline
+    pub fn transcript(&self) -> String {
+        self.lines
+            .borrow()
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+impl OutputSink for BufferSink {
+    fn write_line(&mut self, line: &str) {
+        self.lines.borrow_mut().push(line.to_owned());
+    }
+}
+
+/// Sends every line to both of two sinks
+#[derive(Debug)]
+pub struct TeeSink<A, B>(pub A, pub B);
+
+impl<A: OutputSink, B: OutputSink> OutputSink for TeeSink<A, B> {
+    fn write_line(&mut self, line: &str) {
+        self.0.write_line(line);
+        self.1.write_line(line);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod builtins;
 pub mod environment;
 pub mod executor;
+pub mod files;
+pub mod io;
+pub mod random;
 
 // Re-Exports
-pub use executor::exec;
+pub use executor::Interpreter;
@@ -3,5 +3,5 @@ pub mod interpreter;
 pub mod parser;
 
 // Re-Exports
-pub use interpreter::exec;
+pub use interpreter::Interpreter;
 pub use parser::parser;
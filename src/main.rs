@@ -1,9 +1,8 @@
 use chumsky::Parser;
 use clap::Parser as CLParser;
 use color_eyre::{eyre, install as color_install};
-use rs_pseudocode::{exec, parser};
+use rs_pseudocode::{parser, Interpreter};
 use std::{
-    collections::HashMap,
     fs,
     io::{self, Write},
     process::exit,
@@ -20,29 +19,46 @@ struct Args {
 
     #[arg(short, long)]
     exec: Option<String>,
+
+    /// Seed for RAND, to make runs reproducible
+    #[arg(long)]
+    seed: Option<u64>,
 }
 
 fn main() -> eyre::Result<()> {
     color_install()?;
 
     let args = Args::parse();
+    let interpreter = match args.seed {
+        Some(seed) => Interpreter::new().with_seed(seed),
+        None => Interpreter::new(),
+    };
+
     match args.src {
-        Some(f) => run_file(&f),
+        Some(f) => run_file(&f, interpreter),
         None => match args.exec {
-            Some(src) => run(&src),
-            None => run_repl(),
+            Some(src) => run_program(&src, interpreter),
+            None => run_repl(interpreter),
         },
     }
 
     Ok(())
 }
 
-fn run_file(f: &str) {
+fn run_file(f: &str, interpreter: Interpreter) {
     let src = fs::read_to_string(f).expect("unable to read source file");
-    run(&src);
+    run_program(&src, interpreter);
 }
 
-fn run_repl() {
+fn run_program(src: &str, mut interpreter: Interpreter) {
+    run(src, &mut interpreter);
+
+    if let Err(e) = interpreter.finish() {
+        println!("Exec Error: {:?}", e);
+    }
+}
+
+fn run_repl(mut interpreter: Interpreter) {
     loop {
         let mut line = String::new();
 
@@ -57,17 +73,25 @@ fn run_repl() {
             exit(0);
         }
 
-        run(&line);
+        run(&line, &mut interpreter);
     }
 }
 
-fn run(src: &str) {
+fn run(src: &str, interpreter: &mut Interpreter) {
     match parser().parse(src).into_result() {
         Ok(ast) => {
+            if let Err(e) = interpreter.define(&ast) {
+                println!("Exec Error: {:?}", e);
+                return;
+            }
+
             for stmt in ast.iter() {
-                match exec(stmt, &mut HashMap::new()) {
+                match interpreter.exec(stmt) {
                     Ok(Some(x)) => println!("{}", x),
-                    Err(e) => println!("Exec Error: {:?}", e),
+                    Err(e) => {
+                        println!("Exec Error: {:?}", e);
+                        break;
+                    }
                     _ => {}
                 }
             }
@@ -1,29 +1,453 @@
-use crate::common::{Expr, Operator, Stmt, Value};
+use crate::common::{
+    Access, Array, Bound, CaseLabel, Class, Expr, FileMode, Method, Operator, Param, PassBy, Stmt,
+    Type, TypeDef, Value,
+};
 use chumsky::prelude::*;
+use std::collections::HashMap;
 use time::{Date, Month};
 
 macro_rules! pty {
     ($t: ty) => {
-        impl Parser<'src, &'src str, $t, extra::Err<Rich<'src, char>>> + Clone
+        impl Parser<'src, &'src str, $t, extra::Err<Rich<'src, char>>> + Clone + 'src
     };
 }
 
 pub fn parser<'src>() -> pty!(Vec<Stmt>) {
-    stmt()
-        .padded()
-        .then_ignore(text::newline().or(end()))
-        .repeated()
-        .collect()
+    block(stmt())
+        .then_ignore(comment().or_not())
+        .then_ignore(end())
+        .validate(|mut program, e, emitter| {
+            // Which CONSTANT a name refers to depends on the scope it is in, so they are
+            // folded once the whole program is known
+            for error in fold_constants(&mut program) {
+                emitter.emit(Rich::custom(e.span(), error));
+            }
+
+            program
+        })
 }
 
 /* Statements */
 fn stmt<'src>() -> pty!(Stmt) {
-    stmt_output().or(stmt_expr())
+    recursive(|stmt| {
+        let block = block(stmt.clone());
+
+        choice((
+            stmt_if(block.clone()),
+            stmt_for(block.clone()),
+            stmt_while(block.clone()),
+            stmt_repeat(block.clone()),
+            stmt_class(block.clone()),
+            stmt_procedure(block.clone()),
+            stmt_function(block),
+            stmt_call(),
+            stmt_return(),
+            stmt_case(stmt),
+            stmt_declare(),
+            stmt_constant(),
+            stmt_define(),
+            stmt_type(),
+            stmt_assign(),
+            stmt_output(),
+            stmt_input(),
+            stmt_file(),
+            stmt_expr(),
+        ))
+        .boxed()
+    })
+}
+
+fn block<'src>(stmt: pty!(Stmt)) -> pty!(Vec<Stmt>) {
+    stmt.padded_by(ws())
+        .separated_by(nl())
+        .allow_leading()
+        .allow_trailing()
+        .collect()
+}
+
+fn stmt_if<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    keyword("IF")
+        .ignore_then(expr())
+        .then_ignore(nl().or_not())
+        .then_ignore(keyword("THEN"))
+        .then(block.clone())
+        .then(keyword("ELSE").ignore_then(block).or_not())
+        .then_ignore(keyword("ENDIF"))
+        .map(|((cond, then), otherwise)| Stmt::If(cond, then, otherwise))
+        .boxed()
+}
+
+fn stmt_for<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    let next = keyword("NEXT")
+        .ignore_then(ident().map_with(|name, e| (name, e.span())).or_not())
+        .or(keyword("ENDFOR").to(None));
+
+    keyword("FOR")
+        .ignore_then(ident())
+        .then_ignore(just("<-").padded_by(ws()))
+        .then(expr())
+        .then_ignore(keyword("TO"))
+        .then(expr())
+        .then(keyword("STEP").ignore_then(expr()).or_not())
+        .then(block)
+        .then(next)
+        .validate(|(((((var, from), to), step), body), next), _, emitter| {
+            if let Some((name, span)) = next {
+                if name != var {
+                    emitter.emit(Rich::custom(
+                        span,
+                        format!("expected NEXT {}, found NEXT {}", var, name),
+                    ));
+                }
+            }
+
+            Stmt::For(var, from, to, step, body)
+        })
+        .boxed()
+}
+
+fn stmt_while<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    keyword("WHILE")
+        .ignore_then(expr())
+        .then_ignore(keyword("DO").or_not())
+        .then(block)
+        .then_ignore(keyword("ENDWHILE"))
+        .map(|(cond, body)| Stmt::While(cond, body))
+        .boxed()
+}
+
+fn stmt_repeat<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    keyword("REPEAT")
+        .ignore_then(block)
+        .then_ignore(keyword("UNTIL"))
+        .then(expr())
+        .map(|(body, cond)| Stmt::Repeat(body, cond))
+        .boxed()
+}
+
+fn stmt_case<'src>(stmt: pty!(Stmt)) -> pty!(Stmt) {
+    let label = expr()
+        .then(keyword("TO").ignore_then(expr()).or_not())
+        .map(|(lo, hi)| match hi {
+            Some(hi) => CaseLabel::Range(lo, hi),
+            None => CaseLabel::Value(lo),
+        })
+        .map_with(|label, e| (label, e.span()))
+        .then_ignore(just(':').padded_by(ws()));
+
+    // A clause runs until the next line that starts with a label
+    let body = block(label.clone().not().ignore_then(stmt));
+
+    keyword("CASE")
+        .ignore_then(keyword("OF"))
+        .ignore_then(expr())
+        .then_ignore(nl())
+        .then(label.then(body.clone()).repeated().collect::<Vec<_>>())
+        .then(
+            keyword("OTHERWISE")
+                .ignore_then(just(':').padded_by(ws()).or_not())
+                .ignore_then(body)
+                .or_not(),
+        )
+        .then_ignore(keyword("ENDCASE"))
+        .validate(|((subject, clauses), otherwise), _, emitter| {
+            // Labels that fold to constants are checked here; the rest wait until the CASE runs
+            let mut ranges: Vec<(Value, Value)> = Vec::new();
+            for ((label, span), _) in &clauses {
+                let Some(range) = label.fold() else {
+                    continue;
+                };
+
+                let overlap = ranges
+                    .iter()
+                    .find(|prev| CaseLabel::overlap(&range, prev).unwrap_or(false));
+                if let Some((lo, hi)) = overlap {
+                    emitter.emit(Rich::custom(
+                        *span,
+                        format!(
+                            "CASE label {} TO {} overlaps {} TO {}",
+                            range.0, range.1, lo, hi
+                        ),
+                    ));
+                }
+                ranges.push(range);
+            }
+
+            let clauses = clauses
+                .into_iter()
+                .map(|((label, _), body)| (label, body))
+                .collect();
+            Stmt::Case(subject, clauses, otherwise)
+        })
+        .boxed()
+}
+
+/// A procedure may be called NEW, which makes it the constructor of the CLASS it is in
+fn stmt_procedure<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    keyword("PROCEDURE")
+        .ignore_then(ident().or(text::keyword("NEW").to("NEW".to_owned())))
+        .then(params().or_not())
+        .then(block)
+        .then_ignore(keyword("ENDPROCEDURE"))
+        .map(|((name, params), body)| Stmt::Procedure(name, params.unwrap_or_default(), body))
+        .boxed()
+}
+
+fn stmt_function<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    keyword("FUNCTION")
+        .ignore_then(ident())
+        .then(params().or_not())
+        .then_ignore(keyword("RETURNS"))
+        .then(ty())
+        .then(block)
+        .then_ignore(keyword("ENDFUNCTION"))
+        .map(|(((name, params), returns), body)| {
+            Stmt::Function(name, params.unwrap_or_default(), returns, body)
+        })
+        .boxed()
+}
+
+fn stmt_call<'src>() -> pty!(Stmt) {
+    keyword("CALL")
+        .ignore_then(expr())
+        .try_map(|callee, span| match callee {
+            Expr::Variable(name) => Ok(Stmt::Call(name, Vec::new())),
+            Expr::Call(name, args) => Ok(Stmt::Call(name, args)),
+            Expr::Method(object, method, args) => Ok(Stmt::CallMethod(*object, method, args)),
+            callee => Err(Rich::custom(
+                span,
+                format!("expected a procedure to CALL, found {}", callee),
+            )),
+        })
+        .boxed()
+}
+
+/// Member of a CLASS body
+enum Member {
+    Attribute(Access, String, Type),
+    Method(Method),
+}
+
+fn stmt_class<'src>(block: pty!(Vec<Stmt>)) -> pty!(Stmt) {
+    let access = keyword("PUBLIC")
+        .to(Access::Public)
+        .or(keyword("PRIVATE").to(Access::Private));
+
+    let attribute = access
+        .clone()
+        .then(ident())
+        .then_ignore(just(':').padded_by(ws()))
+        .then(ty())
+        .map(|((access, name), ty)| Member::Attribute(access, name, ty));
+
+    // Methods are PUBLIC unless marked otherwise
+    let method = access
+        .or_not()
+        .then(stmt_procedure(block.clone()).or(stmt_function(block)))
+        .map(|(access, subroutine)| {
+            let access = access.unwrap_or(Access::Public);
+            Member::Method(match subroutine {
+                Stmt::Procedure(name, params, body) => Method {
+                    access,
+                    name,
+                    params,
+                    returns: None,
+                    body,
+                },
+                Stmt::Function(name, params, returns, body) => Method {
+                    access,
+                    name,
+                    params,
+                    returns: Some(returns),
+                    body,
+                },
+                _ => unreachable!(),
+            })
+        });
+
+    keyword("CLASS")
+        .ignore_then(ident())
+        .then(keyword("INHERITS").ignore_then(ident()).or_not())
+        .then(
+            attribute
+                .or(method)
+                .padded_by(ws())
+                .separated_by(nl())
+                .allow_leading()
+                .allow_trailing()
+                .collect::<Vec<_>>(),
+        )
+        .then_ignore(keyword("ENDCLASS"))
+        .map(|((name, parent), members)| {
+            let mut class = Class {
+                parent,
+                attributes: Vec::new(),
+                methods: Vec::new(),
+            };
+
+            for member in members {
+                match member {
+                    Member::Attribute(access, name, ty) => {
+                        class.attributes.push((access, name, ty))
+                    }
+                    Member::Method(method) => class.methods.push(method),
+                }
+            }
+
+            Stmt::Class(name, class)
+        })
+        .boxed()
+}
+
+fn stmt_return<'src>() -> pty!(Stmt) {
+    keyword("RETURN")
+        .ignore_then(expr())
+        .map(Stmt::Return)
+        .boxed()
+}
+
+fn stmt_declare<'src>() -> pty!(Stmt) {
+    keyword("DECLARE")
+        .ignore_then(ident())
+        .then_ignore(just(':').padded_by(ws()))
+        .then(ty())
+        .map(|(name, ty)| Stmt::Declare(name, ty))
+        .boxed()
+}
+
+fn stmt_constant<'src>() -> pty!(Stmt) {
+    keyword("CONSTANT")
+        .ignore_then(ident())
+        .then_ignore(just('=').padded_by(ws()))
+        .then(expr())
+        .map(|(name, value)| Stmt::Constant(name, value))
+        .boxed()
+}
+
+fn stmt_define<'src>() -> pty!(Stmt) {
+    keyword("DEFINE")
+        .ignore_then(ident())
+        .then(args())
+        .then_ignore(just(':').padded_by(ws()))
+        .then(ident())
+        .map(|((name, elements), ty)| Stmt::Define(name, elements, ty))
+        .boxed()
+}
+
+fn stmt_type<'src>() -> pty!(Stmt) {
+    let field = keyword("DECLARE")
+        .ignore_then(ident())
+        .then_ignore(just(':').padded_by(ws()))
+        .then(ty());
+
+    let record = field
+        .padded_by(ws())
+        .separated_by(nl())
+        .allow_leading()
+        .allow_trailing()
+        .at_least(1)
+        .collect()
+        .then_ignore(keyword("ENDTYPE"))
+        .map(TypeDef::Record);
+
+    let members = ident()
+        .padded_by(ws())
+        .separated_by(just(','))
+        .at_least(1)
+        .collect()
+        .delimited_by(just('('), just(')'))
+        .map(TypeDef::Enum);
+
+    let pointer = just('^').ignore_then(ty()).map(TypeDef::Pointer);
+
+    let set = keyword("SET")
+        .ignore_then(keyword("OF"))
+        .ignore_then(ty())
+        .map(TypeDef::Set);
+
+    keyword("TYPE")
+        .ignore_then(ident())
+        .then(choice((
+            just('=')
+                .padded_by(ws())
+                .ignore_then(choice((members, pointer, set))),
+            record,
+        )))
+        .map(|(name, def)| Stmt::TypeDef(name, def))
+        .boxed()
+}
+
+fn stmt_assign<'src>() -> pty!(Stmt) {
+    lvalue()
+        .then_ignore(just("<-").padded_by(ws()))
+        .then(expr())
+        .map(|(target, value)| Stmt::Assign(target, value))
+        .boxed()
+}
+
+fn stmt_input<'src>() -> pty!(Stmt) {
+    keyword("INPUT")
+        .ignore_then(lvalue())
+        .map(Stmt::Input)
+        .boxed()
+}
+
+fn stmt_file<'src>() -> pty!(Stmt) {
+    let comma = || just(',').padded_by(ws());
+
+    let mode = choice((
+        keyword("READ").to(FileMode::Read),
+        keyword("WRITE").to(FileMode::Write),
+        keyword("APPEND").to(FileMode::Append),
+        keyword("RANDOM").to(FileMode::Random),
+    ));
+
+    let open = keyword("OPENFILE")
+        .ignore_then(expr())
+        .then_ignore(keyword("FOR"))
+        .then(mode)
+        .map(|(file, mode)| Stmt::OpenFile(file, mode));
+
+    let read = keyword("READFILE")
+        .ignore_then(expr())
+        .then_ignore(comma())
+        .then(lvalue())
+        .map(|(file, target)| Stmt::ReadFile(file, target));
+
+    let write = keyword("WRITEFILE")
+        .ignore_then(expr())
+        .then_ignore(comma())
+        .then(expr())
+        .map(|(file, data)| Stmt::WriteFile(file, data));
+
+    let close = keyword("CLOSEFILE")
+        .ignore_then(expr())
+        .map(Stmt::CloseFile);
+
+    let seek = keyword("SEEK")
+        .ignore_then(expr())
+        .then_ignore(comma())
+        .then(expr())
+        .map(|(file, address)| Stmt::Seek(file, address));
+
+    let get = keyword("GETRECORD")
+        .ignore_then(expr())
+        .then_ignore(comma())
+        .then(lvalue())
+        .map(|(file, target)| Stmt::GetRecord(file, target));
+
+    let put = keyword("PUTRECORD")
+        .ignore_then(expr())
+        .then_ignore(comma())
+        .then(expr())
+        .map(|(file, record)| Stmt::PutRecord(file, record));
+
+    choice((open, read, write, close, seek, get, put)).boxed()
 }
 
 fn stmt_output<'src>() -> pty!(Stmt) {
-    text::keyword("OUTPUT")
-        .ignore_then(expr().padded().separated_by(just(',')).collect())
+    keyword("OUTPUT")
+        .ignore_then(expr().padded_by(ws()).separated_by(just(',')).collect())
         .map(Stmt::Output)
         .boxed()
 }
@@ -34,24 +458,53 @@ fn stmt_expr<'src>() -> pty!(Stmt) {
 
 /* Expressions */
 fn expr<'src>() -> pty!(Expr) {
-    let jp = |c| just(c).padded();
+    let jp = |c| just(c).padded_by(ws());
 
-    let variable = text::ident()
-        .and_is(kw().not())
-        .map(|s: &str| s.to_owned())
-        .map(Expr::Variable);
+    let variable = ident().map(Expr::Variable);
 
     recursive(|expr| {
         let literal = literal().map(Expr::Literal);
 
+        let arguments = expr
+            .clone()
+            .padded_by(ws())
+            .separated_by(just(','))
+            .collect::<Vec<_>>()
+            .delimited_by(just('('), just(')'))
+            .boxed();
+
+        let call = ident()
+            .then(arguments.clone())
+            .map(|(name, args)| Expr::Call(name, args));
+
+        let address = just('@')
+            .ignore_then(ident().map(Expr::Variable))
+            .foldl(postfix(expr.clone()).repeated(), Postfix::apply)
+            .map(|target| Expr::AddressOf(Box::new(target)));
+
+        let new = keyword("NEW")
+            .ignore_then(ty())
+            .then(ws().ignore_then(arguments).or_not())
+            .map(|(ty, args)| Expr::New(ty, args.unwrap_or_default()));
+
+        let this = keyword("SUPER").to(Expr::Super);
+
         let atom = literal
-            .or(expr.delimited_by(just('('), just(')')))
+            .or(expr
+                .clone()
+                .padded_by(ws())
+                .delimited_by(just('('), just(')')))
+            .or(address)
+            .or(new)
+            .or(this)
+            .or(call)
             .or(variable)
+            .foldl(postfix(expr).repeated(), Postfix::apply)
             .boxed();
 
         let unary = jp("-")
             .to(Operator::Minus)
-            .or(jp("NOT").to(Operator::Not))
+            .or(keyword("NOT").to(Operator::Not))
             .repeated()
             .foldr(atom, |op, rhs| Expr::Unary(op, Box::new(rhs)))
             .boxed();
@@ -63,8 +516,9 @@ fn expr<'src>() -> pty!(Expr) {
                     choice((
                         jp("*").to(Operator::Multiply),
                         jp("/").to(Operator::Divide),
-                        jp("DIV").to(Operator::Quotient),
-                        jp("MOD").to(Operator::Remainder),
+                        keyword("DIV").to(Operator::Quotient),
+                        keyword("MOD").to(Operator::Remainder),
+                        keyword("INTERSECTION").to(Operator::Intersection),
                     ))
                     .then(unary)
                     .repeated(),
@@ -75,9 +529,14 @@ fn expr<'src>() -> pty!(Expr) {
             let sum = product
                 .clone()
                 .foldl(
-                    choice((jp("+").to(Operator::Plus), jp("-").to(Operator::Minus)))
-                        .then(product)
-                        .repeated(),
+                    choice((
+                        jp("+").to(Operator::Plus),
+                        jp("-").to(Operator::Minus),
+                        keyword("UNION").to(Operator::Union),
+                        keyword("DIFFERENCE").to(Operator::Difference),
+                    ))
+                    .then(product)
+                    .repeated(),
                     |lhs, (op, rhs)| Expr::Binary(Box::new(lhs), op, Box::new(rhs)),
                 )
                 .boxed();
@@ -90,6 +549,7 @@ fn expr<'src>() -> pty!(Expr) {
                         jp("<=").to(Operator::Le),
                         jp(">").to(Operator::Gt),
                         jp("<").to(Operator::Lt),
+                        keyword("IN").to(Operator::In),
                     ))
                     .then(sum)
                     .repeated(),
@@ -110,23 +570,489 @@ fn expr<'src>() -> pty!(Expr) {
             let and = comparison2
                 .clone()
                 .foldl(
-                    jp("AND").to(Operator::And).then(comparison2).repeated(),
+                    keyword("AND")
+                        .to(Operator::And)
+                        .then(comparison2)
+                        .repeated(),
                     |lhs, (op, rhs)| Expr::Binary(Box::new(lhs), op, Box::new(rhs)),
                 )
                 .boxed();
 
-            let or = and.clone().foldl(
-                jp("OR").to(Operator::Or).then(and).repeated(),
+            and.clone().foldl(
+                keyword("OR").to(Operator::Or).then(and).repeated(),
                 |lhs, (op, rhs)| Expr::Binary(Box::new(lhs), op, Box::new(rhs)),
-            );
-
-            or
+            )
         };
 
         binary
     })
 }
 
+/// Assignment target: a variable followed by any number of subscripts and field accesses
+fn lvalue<'src>() -> pty!(Expr) {
+    ident()
+        .map(Expr::Variable)
+        .foldl(postfix(expr()).repeated(), Postfix::apply)
+        .boxed()
+}
+
+#[derive(Clone)]
+enum Postfix {
+    Index(Vec<Expr>),
+    Field(String),
+    Method(String, Vec<Expr>),
+    Deref,
+}
+
+impl Postfix {
+    fn apply(base: Expr, postfix: Postfix) -> Expr {
+        match postfix {
+            Postfix::Index(indices) => Expr::Index(Box::new(base), indices),
+            Postfix::Field(field) => Expr::Field(Box::new(base), field),
+            Postfix::Method(method, args) => Expr::Method(Box::new(base), method, args),
+            Postfix::Deref => Expr::Deref(Box::new(base)),
+        }
+    }
+}
+
+fn postfix<'src>(expr: pty!(Expr)) -> pty!(Postfix) {
+    let subscript = expr
+        .clone()
+        .padded_by(ws())
+        .separated_by(just(','))
+        .at_least(1)
+        .collect()
+        .delimited_by(ws().then(just('[')), just(']'))
+        .map(Postfix::Index);
+
+    let field = just('.').ignore_then(ident()).map(Postfix::Field);
+
+    // A method may be the constructor NEW, as in SUPER.NEW(...)
+    let method = just('.')
+        .ignore_then(ident().or(text::keyword("NEW").to("NEW".to_owned())))
+        .then(
+            expr.padded_by(ws())
+                .separated_by(just(','))
+                .collect()
+                .delimited_by(just('('), just(')')),
+        )
+        .map(|(method, args)| Postfix::Method(method, args));
+
+    let deref = just('^').to(Postfix::Deref);
+
+    choice((subscript, method, field, deref)).boxed()
+}
+
+/* Parameters */
+
+/// Parenthesised parameter list. A BYREF or BYVAL marker applies to every
+/// following parameter until the next marker, with BYVAL assumed at the start.
+fn params<'src>() -> pty!(Vec<Param>) {
+    let mode = keyword("BYREF")
+        .to(PassBy::Reference)
+        .or(keyword("BYVAL").to(PassBy::Value));
+
+    mode.or_not()
+        .then(ident())
+        .then_ignore(just(':').padded_by(ws()))
+        .then(ty())
+        .separated_by(just(',').padded_by(ws()))
+        .collect::<Vec<_>>()
+        .delimited_by(just('(').padded_by(ws()), just(')').padded_by(ws()))
+        .map(|params| {
+            let mut mode = PassBy::Value;
+
+            params
+                .into_iter()
+                .map(|((marker, name), ty)| {
+                    mode = marker.unwrap_or(mode);
+                    Param { name, ty, mode }
+                })
+                .collect()
+        })
+        .boxed()
+}
+
+fn args<'src>() -> pty!(Vec<Expr>) {
+    expr()
+        .padded_by(ws())
+        .separated_by(just(','))
+        .collect()
+        .delimited_by(just('(').padded_by(ws()), just(')').padded_by(ws()))
+        .boxed()
+}
+
+/* Types */
+fn ty<'src>() -> pty!(Type) {
+    let literal_bound =
+        just('-')
+            .or_not()
+            .then(text::int(10))
+            .to_slice()
+            .try_map(|s: &str, span| {
+                s.parse()
+                    .map_err(|_| Rich::custom(span, format!("{} is too large for an INTEGER", s)))
+            });
+
+    let bound = literal_bound
+        .map(Bound::Integer)
+        .or(ident().map(Bound::Constant))
+        .boxed();
+
+    let bounds = bound
+        .clone()
+        .then_ignore(just(':').padded_by(ws()))
+        .then(bound)
+        .padded_by(ws())
+        .separated_by(just(','))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(just('['), just(']'));
+
+    let element = basic_ty().or(ident().map(Type::Named));
+
+    let array = keyword("ARRAY")
+        .ignore_then(bounds)
+        .then_ignore(keyword("OF"))
+        .then(element.clone())
+        .validate(|(bounds, ty), e, emitter| {
+            let literal = bounds
+                .iter()
+                .map(|bound| match bound {
+                    (Bound::Integer(lo), Bound::Integer(hi)) => Some((*lo, *hi)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+
+            // Bounds naming CONSTANTs are checked once their values are folded
+            let Some(literal) = literal else {
+                return Type::ArrayOf(bounds, Box::new(ty));
+            };
+
+            if let Err(error) = check_bounds(&literal, &ty) {
+                emitter.emit(Rich::custom(e.span(), error));
+            }
+            Type::Array(literal, Box::new(ty))
+        });
+
+    array.or(element).boxed()
+}
+
+/// Checks an ARRAY of `ty` with `bounds` is one the interpreter can create
+fn check_bounds(bounds: &[(i64, i64)], ty: &Type) -> Result<(), String> {
+    if let Some((lo, hi)) = bounds.iter().find(|(lo, hi)| lo > hi) {
+        return Err(format!(
+            "lower bound {} is greater than upper bound {}",
+            lo, hi
+        ));
+    }
+
+    if Array::len(bounds).is_none() {
+        let ty = Type::Array(bounds.to_vec(), Box::new(ty.clone()));
+        return Err(format!("{} has more than {} elements", ty, Array::MAX_LEN));
+    }
+
+    Ok(())
+}
+
+fn basic_ty<'src>() -> pty!(Type) {
+    choice((
+        keyword("INTEGER").to(Type::Integer),
+        keyword("REAL").to(Type::Real),
+        keyword("CHAR").to(Type::Char),
+        keyword("STRING").to(Type::String),
+        keyword("BOOLEAN").to(Type::Boolean),
+        keyword("DATE").to(Type::Date),
+    ))
+    .boxed()
+}
+
+/* Constants */
+
+/// Works out the value of every CONSTANT in `program`, putting it in place of the
+/// CONSTANT's expression and of any array bound naming it. Yields a message for each
+/// value that cannot be known before running.
+fn fold_constants(program: &mut [Stmt]) -> Vec<String> {
+    let mut folder = Folder {
+        scopes: vec![Scope {
+            names: HashMap::new(),
+            frame: true,
+        }],
+        attributes: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    folder.block(program);
+    folder.errors
+}
+
+/// Walks a program keeping track of names in scope the way the interpreter does
+struct Folder {
+    scopes: Vec<Scope>,
+    /// Attributes of each CLASS so far, inherited ones included
+    attributes: HashMap<String, Vec<String>>,
+    errors: Vec<String>,
+}
+
+struct Scope {
+    /// Value of each CONSTANT, or `None` for a variable or parameter, which hides
+    /// any CONSTANT of the same name further out
+    names: HashMap<String, Option<Value>>,
+    /// Set for the body of a subroutine, which sees the globals but not its caller's locals
+    frame: bool,
+}
+
+impl Folder {
+    fn block(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    /// Runs `f` in a new scope in which `names` are variables
+    fn scoped(&mut self, frame: bool, names: Vec<String>, f: impl FnOnce(&mut Self)) {
+        let names = names.into_iter().map(|name| (name, None)).collect();
+        self.scopes.push(Scope { names, frame });
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Declares `name` in the innermost scope, unless it is already taken there
+    fn declare(&mut self, name: &str, value: Option<Value>) {
+        self.scopes
+            .last_mut()
+            .expect("global scope always exists")
+            .names
+            .entry(name.to_owned())
+            .or_insert(value);
+    }
+
+    /// Value of the CONSTANT `name` refers to in the innermost scope, if it refers to one
+    fn constant(&self, name: &str) -> Option<Value> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(value) = scope.names.get(name) {
+                return value.clone();
+            }
+
+            if scope.frame {
+                break;
+            }
+        }
+
+        self.scopes[0].names.get(name).cloned().flatten()
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Declare(name, ty) => {
+                self.ty(ty);
+                self.declare(name, None);
+            }
+            Stmt::Constant(name, value) => match value.fold_with(&|name| self.constant(name)) {
+                Some(folded) => {
+                    *value = Expr::Literal(folded.clone());
+                    self.declare(name, Some(folded));
+                }
+                None => {
+                    self.errors.push(format!(
+                        "value of a CONSTANT must be known before running: {}",
+                        value
+                    ));
+                    self.declare(name, None);
+                }
+            },
+            Stmt::TypeDef(_, def) => match def {
+                TypeDef::Record(fields) => fields.iter_mut().for_each(|(_, ty)| self.ty(ty)),
+                TypeDef::Pointer(ty) | TypeDef::Set(ty) => self.ty(ty),
+                TypeDef::Enum(_) | TypeDef::Class => {}
+            },
+            Stmt::Define(name, elements, _) => {
+                self.exprs(elements);
+                self.declare(name, None);
+            }
+            Stmt::If(cond, then, otherwise) => {
+                self.expr(cond);
+                self.block(then);
+                self.block(otherwise.as_deref_mut().unwrap_or_default());
+            }
+            Stmt::Case(subject, clauses, otherwise) => {
+                self.expr(subject);
+                for (label, body) in clauses {
+                    match label {
+                        CaseLabel::Value(v) => self.expr(v),
+                        CaseLabel::Range(lo, hi) => {
+                            self.expr(lo);
+                            self.expr(hi);
+                        }
+                    }
+                    self.block(body);
+                }
+                self.block(otherwise.as_deref_mut().unwrap_or_default());
+            }
+            Stmt::For(_, from, to, step, body) => {
+                self.expr(from);
+                self.expr(to);
+                if let Some(step) = step {
+                    self.expr(step);
+                }
+                self.scoped(false, Vec::new(), |folder| folder.block(body));
+            }
+            Stmt::While(cond, body) => {
+                self.expr(cond);
+                self.scoped(false, Vec::new(), |folder| folder.block(body));
+            }
+            Stmt::Repeat(body, cond) => self.scoped(false, Vec::new(), |folder| {
+                folder.block(body);
+                folder.expr(cond);
+            }),
+            Stmt::Procedure(_, params, body) => self.subroutine(params, None, body, Vec::new()),
+            Stmt::Function(_, params, returns, body) => {
+                self.subroutine(params, Some(returns), body, Vec::new())
+            }
+            Stmt::Class(name, class) => {
+                let mut attributes = class
+                    .parent
+                    .as_ref()
+                    .and_then(|parent| self.attributes.get(parent))
+                    .cloned()
+                    .unwrap_or_default();
+                for (_, attribute, ty) in &mut class.attributes {
+                    self.ty(ty);
+                    attributes.push(attribute.clone());
+                }
+
+                for method in &mut class.methods {
+                    let Method {
+                        params,
+                        returns,
+                        body,
+                        ..
+                    } = method;
+                    self.subroutine(params, returns.as_mut(), body, attributes.clone());
+                }
+                self.attributes.insert(name.clone(), attributes);
+            }
+            Stmt::Expression(a)
+            | Stmt::Input(a)
+            | Stmt::Return(a)
+            | Stmt::OpenFile(a, _)
+            | Stmt::CloseFile(a) => self.expr(a),
+            Stmt::Assign(a, b)
+            | Stmt::ReadFile(a, b)
+            | Stmt::WriteFile(a, b)
+            | Stmt::Seek(a, b)
+            | Stmt::GetRecord(a, b)
+            | Stmt::PutRecord(a, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+            Stmt::Output(exprs) | Stmt::Call(_, exprs) => self.exprs(exprs),
+            Stmt::CallMethod(object, _, args) => {
+                self.expr(object);
+                self.exprs(args);
+            }
+        }
+    }
+
+    /// Folds the types a subroutine is declared with where it is defined, then its body
+    /// in a frame of its own, in which `attributes` and its parameters are variables
+    fn subroutine(
+        &mut self,
+        params: &mut [Param],
+        returns: Option<&mut Type>,
+        body: &mut [Stmt],
+        attributes: Vec<String>,
+    ) {
+        for param in params.iter_mut() {
+            self.ty(&mut param.ty);
+        }
+        if let Some(returns) = returns {
+            self.ty(returns);
+        }
+
+        let mut names = attributes;
+        names.extend(params.iter().map(|param| param.name.clone()));
+        self.scoped(true, names, |folder| folder.block(body));
+    }
+
+    /// Folds the type of any NEW within `expr`
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Literal(_) | Expr::Variable(_) | Expr::Super => {}
+            Expr::Unary(_, a) | Expr::Field(a, _) | Expr::AddressOf(a) | Expr::Deref(a) => {
+                self.expr(a)
+            }
+            Expr::Binary(a, _, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+            Expr::Call(_, args) => self.exprs(args),
+            Expr::Index(a, args) | Expr::Method(a, _, args) => {
+                self.expr(a);
+                self.exprs(args);
+            }
+            Expr::New(ty, args) => {
+                self.ty(ty);
+                self.exprs(args);
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    /// Puts the values of the CONSTANTs that array bounds in `ty` name in their place
+    fn ty(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Array(_, element) | Type::Pointer(element) => self.ty(element),
+            Type::ArrayOf(bounds, element) => {
+                self.ty(element);
+
+                let Some(bounds) = bounds
+                    .iter()
+                    .map(|(lo, hi)| Some((self.bound(lo)?, self.bound(hi)?)))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    return;
+                };
+
+                match check_bounds(&bounds, element) {
+                    Ok(()) => *ty = Type::Array(bounds, element.clone()),
+                    Err(error) => self.errors.push(error),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn bound(&mut self, bound: &Bound) -> Option<i64> {
+        let name = match bound {
+            Bound::Integer(i) => return Some(*i),
+            Bound::Constant(name) => name,
+        };
+
+        match self.constant(name) {
+            Some(Value::Integer(i)) => Some(i),
+            Some(value) => {
+                self.errors.push(format!(
+                    "array bound {} must be an INTEGER, not {}",
+                    name,
+                    value.ty()
+                ));
+                None
+            }
+            None => {
+                self.errors
+                    .push(format!("array bound {} is not a CONSTANT", name));
+                None
+            }
+        }
+    }
+}
+
 /* Literals */
 fn literal<'src>() -> pty!(Value) {
     let c_escape = just('\\').ignore_then(
@@ -155,7 +1081,10 @@ fn literal<'src>() -> pty!(Value) {
         .boxed();
 
     let int = text::int(10)
-        .map(|s: &str| s.parse().unwrap())
+        .try_map(|s: &str, span| {
+            s.parse()
+                .map_err(|_| Rich::custom(span, format!("{} is too large for an INTEGER", s)))
+        })
         .map(Value::Integer)
         .boxed();
 
@@ -179,14 +1108,25 @@ fn literal<'src>() -> pty!(Value) {
         .map(Value::String)
         .boxed();
 
-    let date = n_digits(2, 10)
+    // Day and month may drop their leading zero, as in `1/2/2024`
+    let date = n_digits(1, 2, 10)
         .then_ignore(just('/'))
-        .then(n_digits(2, 10))
+        .then(n_digits(1, 2, 10))
         .then_ignore(just('/'))
-        .then(n_digits(4, 10))
+        .then(n_digits(4, 4, 10))
         .delimited_by(just('`'), just('`'))
-        .map(|((d, m), y)| {
-            Date::from_calendar_date(y as i32, Month::try_from(m as u8).unwrap(), d as u8).unwrap()
+        .validate(|((d, m), y), e, emitter| {
+            let date = Month::try_from(m as u8)
+                .ok()
+                .and_then(|month| Date::from_calendar_date(y as i32, month, d as u8).ok());
+
+            date.unwrap_or_else(|| {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    format!("{:02}/{:02}/{:04} is not a valid date", d, m, y),
+                ));
+                Date::MIN
+            })
         })
         .map(Value::Date)
         .boxed();
@@ -194,7 +1134,70 @@ fn literal<'src>() -> pty!(Value) {
     choice((real, int, r#bool, r#char, r#str, date))
 }
 
+/// Converts a line typed in response to INPUT into a value of type `ty`, following
+/// the rules for literals. STRING and CHAR input need no quotes, DATE input needs
+/// no backticks and numbers may be negative.
+pub fn input_value(line: &str, ty: &Type) -> Option<Value> {
+    let literal = |s: &str| literal().then_ignore(end()).parse(s).into_result().ok();
+    let line = line.trim_end_matches(['\r', '\n']);
+
+    let value = match ty {
+        Type::String if !line.starts_with('"') => Value::String(line.to_owned()),
+        Type::Char if line.chars().count() == 1 => Value::Character(line.chars().next()?),
+        Type::Date if !line.trim().starts_with('`') => literal(&format!("`{}`", line.trim()))?,
+        Type::Integer | Type::Real => number(line)?,
+        _ => literal(line.trim())?,
+    };
+
+    ty.coerce(value)
+}
+
+/// Reads `s` as an INTEGER or REAL literal, which may be negative. Whether it has
+/// a fractional part decides which of the two it is.
+pub fn number(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, s),
+    };
+
+    let value = literal().then_ignore(end()).parse(s).into_result().ok()?;
+    match value {
+        Value::Integer(_) | Value::Real(_) if negative => value.neg().ok(),
+        Value::Integer(_) | Value::Real(_) => Some(value),
+        _ => None,
+    }
+}
+
 /* Helpers */
+fn ident<'src>() -> pty!(String) {
+    text::ident().and_is(kw().not()).map(|s: &str| s.to_owned())
+}
+
+fn keyword<'src>(keyword: &'static str) -> pty!(()) {
+    text::keyword(keyword).ignored().padded_by(ws())
+}
+
+fn ws<'src>() -> pty!(()) {
+    text::inline_whitespace().ignored()
+}
+
+fn comment<'src>() -> pty!(()) {
+    just("//")
+        .then(any().and_is(text::newline().not()).repeated())
+        .ignored()
+}
+
+/// Line break between statements, swallowing any blank or comment-only lines
+fn nl<'src>() -> pty!(()) {
+    ws().then(comment().or_not())
+        .then(text::newline())
+        .repeated()
+        .at_least(1)
+        .then(ws())
+        .ignored()
+}
+
 fn kw<'src>() -> pty!(()) {
     choice(vec![
         // IO
@@ -221,6 +1224,15 @@ fn kw<'src>() -> pty!(()) {
         text::keyword("ARRAY"),
         text::keyword("TYPE"),
         text::keyword("ENDTYPE"),
+        text::keyword("SET"),
+        text::keyword("DEFINE"),
+        // Classes
+        text::keyword("CLASS"),
+        text::keyword("ENDCLASS"),
+        text::keyword("INHERITS"),
+        text::keyword("PUBLIC"),
+        text::keyword("PRIVATE"),
+        text::keyword("SUPER"),
         // Selection
         text::keyword("IF"),
         text::keyword("THEN"),
@@ -244,27 +1256,76 @@ fn kw<'src>() -> pty!(()) {
         // Declaration
         text::keyword("DECLARE"),
         text::keyword("CONSTANT"),
+        text::keyword("NEW"),
         // Operators
         text::keyword("MOD"),
         text::keyword("DIV"),
         text::keyword("AND"),
         text::keyword("OR"),
         text::keyword("NOT"),
+        text::keyword("IN"),
+        text::keyword("UNION"),
+        text::keyword("INTERSECTION"),
+        text::keyword("DIFFERENCE"),
         // Functions or Procedures
         text::keyword("PROCEDURE"),
         text::keyword("ENDPROCEDURE"),
         text::keyword("CALL"),
         text::keyword("FUNCTION"),
+        text::keyword("RETURN"),
         text::keyword("RETURNS"),
         text::keyword("BYREF"),
+        text::keyword("BYVAL"),
         text::keyword("ENDFUNCTION"),
     ])
     .ignored()
 }
 
-fn n_digits<'src>(n: usize, radix: u32) -> pty!(u32) {
+/// Number written with between `min` and `max` digits
+fn n_digits<'src>(min: usize, max: usize, radix: u32) -> pty!(u32) {
     text::digits(radix)
-        .exactly(n)
+        .at_least(min)
+        .at_most(max)
         .collect()
         .map(|s: String| s.parse().expect("infallible"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    fn date_literal(src: &str) -> Result<Value, Vec<Rich<'_, char>>> {
+        literal().then_ignore(end()).parse(src).into_result()
+    }
+
+    #[test]
+    fn dates_may_drop_leading_zeros() {
+        for src in ["`01/02/2024`", "`1/2/2024`", "`1/02/2024`"] {
+            assert_eq!(
+                date_literal(src).unwrap(),
+                Value::Date(date!(2024 - 02 - 01))
+            );
+        }
+        assert!(date_literal("`001/02/2024`").is_err());
+        assert!(date_literal("`01/02/24`").is_err());
+    }
+
+    #[test]
+    fn impossible_dates_are_reported_with_their_span() {
+        for src in [
+            "`31/02/2024`",
+            "`01/13/2024`",
+            "`00/01/2024`",
+            "`29/2/2023`",
+        ] {
+            let errors = date_literal(src).unwrap_err();
+
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].span().into_range(), 0..src.len());
+            assert!(errors[0].to_string().contains("is not a valid date"));
+        }
+
+        assert_eq!(input_value("31/02/2024", &Type::Date), None);
+    }
+}
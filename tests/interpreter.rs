@@ -0,0 +1,1229 @@
+use chumsky::Parser;
+use rs_pseudocode::{
+    common::{EvalError, Type, Value, ValueError},
+    interpreter::{
+        executor::Error,
+        files::MemoryFileSystem,
+        io::{BufferSink, ScriptedInput, TeeSink},
+    },
+    parser, Interpreter,
+};
+use time::macros::date;
+
+fn run(src: &str) -> Interpreter {
+    let ast = parser()
+        .parse(src)
+        .into_result()
+        .expect("program should parse");
+    let mut interpreter = Interpreter::new();
+    interpreter.run(&ast).expect("program should run");
+    interpreter
+}
+
+#[test]
+fn state_persists_across_statements() {
+    let interpreter = run("DECLARE x : INTEGER\nDECLARE r : REAL\nx <- 3 + 4\nr <- x * 2");
+
+    assert_eq!(interpreter.env().get_value("x"), Some(Value::Integer(7)));
+    assert_eq!(interpreter.env().get_value("r"), Some(Value::Real(14.0)));
+}
+
+#[test]
+fn declare_gives_default_values() {
+    let interpreter = run("DECLARE i : INTEGER
+DECLARE r : REAL
+DECLARE c : CHAR
+DECLARE s : STRING
+DECLARE b : BOOLEAN
+DECLARE d : DATE");
+    let value = |name| interpreter.env().get_value(name);
+
+    assert_eq!(value("i"), Some(Value::Integer(0)));
+    assert_eq!(value("r"), Some(Value::Real(0.0)));
+    assert_eq!(value("c"), Some(Value::Character(' ')));
+    assert_eq!(value("s"), Some(Value::String(String::new())));
+    assert_eq!(value("b"), Some(Value::Boolean(false)));
+    assert_eq!(value("d"), Some(Value::Date(date!(1970 - 01 - 01))));
+
+    let ast = parser()
+        .parse("DECLARE x : INTEGER\nDECLARE x : REAL")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::AlreadyDeclared(name)) if name == "x"
+    ));
+}
+
+#[test]
+fn assignment_enforces_declared_types() {
+    let interpreter = run("DECLARE r : REAL\nr <- 2");
+    assert_eq!(interpreter.env().get_value("r"), Some(Value::Real(2.0)));
+
+    let ast = parser()
+        .parse("DECLARE x : INTEGER\nx <- 3.5")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::TypeMismatch {
+            expected: Type::Integer,
+            found: Type::Real
+        })
+    ));
+}
+
+#[test]
+fn nested_if_else() {
+    let interpreter = run("DECLARE x : INTEGER
+DECLARE s : STRING
+x <- 5
+IF x > 3
+  THEN
+    IF x > 10
+      THEN
+        s <- \"huge\"
+      ELSE
+        s <- \"big\"
+    ENDIF
+  ELSE
+    s <- \"small\"
+ENDIF");
+
+    assert_eq!(
+        interpreter.env().get_value("s"),
+        Some(Value::String("big".into()))
+    );
+}
+
+#[test]
+fn case_ranges_and_otherwise() {
+    let interpreter = run("DECLARE c : CHAR
+DECLARE s : STRING
+c <- 'q'
+CASE OF c
+  'a' : s <- \"a\"
+  'b' TO 'z' : s <- \"lower\"
+  OTHERWISE : s <- \"other\"
+ENDCASE");
+
+    assert_eq!(
+        interpreter.env().get_value("s"),
+        Some(Value::String("lower".into()))
+    );
+}
+
+#[test]
+fn case_rejects_overlapping_labels() {
+    assert!(parser()
+        .parse("CASE OF 3\n  1 TO 5 : OUTPUT 1\n  5 : OUTPUT 2\nENDCASE")
+        .has_errors());
+
+    // Labels naming variables are only known once the CASE runs
+    let ast = parser()
+        .parse(
+            "DECLARE n : INTEGER\nn <- 5\nCASE OF 3\n  1 TO 5 : OUTPUT 1\n  n : OUTPUT 2\nENDCASE",
+        )
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::OverlappingCaseLabels(first, second)) if first == "1 TO 5" && second == "5 TO 5"
+    ));
+}
+
+#[test]
+fn for_loop_with_negative_step() {
+    let interpreter = run("DECLARE i : INTEGER
+DECLARE s : STRING
+FOR i <- 10 TO 1 STEP -3
+  s <- s + \"x\"
+NEXT i");
+
+    assert_eq!(
+        interpreter.env().get_value("s"),
+        Some(Value::String("xxxx".into()))
+    );
+}
+
+#[test]
+fn for_loop_scopes_each_iteration() {
+    let interpreter = run("DECLARE i : INTEGER
+DECLARE total : INTEGER
+FOR i <- 1 TO 3
+  DECLARE square : INTEGER
+  square <- i * i
+  total <- total + square
+NEXT i
+FOR i <- 9223372036854775806 TO 9223372036854775807
+  total <- total + 1
+NEXT i");
+
+    assert_eq!(
+        interpreter.env().get_value("total"),
+        Some(Value::Integer(16))
+    );
+    assert_eq!(interpreter.env().get_value("square"), None);
+}
+
+#[test]
+fn for_loop_rejects_zero_step() {
+    let ast = parser()
+        .parse("DECLARE i : INTEGER\nFOR i <- 1 TO 3 STEP 0\nNEXT i")
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(Interpreter::new().run(&ast), Err(Error::ZeroStep)));
+}
+
+#[test]
+fn for_loop_checks_next_identifier() {
+    assert!(parser()
+        .parse("FOR i <- 1 TO 2\nNEXT j")
+        .into_result()
+        .is_err());
+}
+
+#[test]
+fn while_and_repeat_loops() {
+    let interpreter = run("DECLARE n : INTEGER
+DECLARE m : INTEGER
+WHILE n < 5 DO
+  n <- n + 1
+ENDWHILE
+WHILE n < 8
+  n <- n + 1
+ENDWHILE
+REPEAT
+  m <- m + 1
+UNTIL m >= 3");
+
+    assert_eq!(interpreter.env().get_value("n"), Some(Value::Integer(8)));
+    assert_eq!(interpreter.env().get_value("m"), Some(Value::Integer(3)));
+}
+
+#[test]
+fn while_and_repeat_scope_each_iteration() {
+    let interpreter = run("DECLARE n : INTEGER
+DECLARE m : INTEGER
+WHILE n < 3
+  DECLARE step : INTEGER
+  step <- 1
+  n <- n + step
+ENDWHILE
+REPEAT
+  DECLARE next : INTEGER
+  next <- m + 2
+  m <- next
+UNTIL next >= 6");
+
+    assert_eq!(interpreter.env().get_value("n"), Some(Value::Integer(3)));
+    assert_eq!(interpreter.env().get_value("m"), Some(Value::Integer(6)));
+    assert_eq!(interpreter.env().get_value("next"), None);
+}
+
+#[test]
+fn loop_conditions_must_be_boolean() {
+    for src in ["WHILE 1\nENDWHILE", "REPEAT\nUNTIL \"yes\""] {
+        let ast = parser().parse(src).into_result().unwrap();
+        assert!(
+            matches!(
+                Interpreter::new().run(&ast),
+                Err(Error::TypeMismatch {
+                    expected: Type::Boolean,
+                    ..
+                })
+            ),
+            "{}",
+            src
+        );
+    }
+}
+
+#[test]
+fn procedures_pass_byref_and_byval() {
+    let interpreter = run("DECLARE x : INTEGER
+DECLARE y : INTEGER
+PROCEDURE Update(BYVAL a : INTEGER, BYREF b : INTEGER)
+  a <- a * 10
+  b <- b * 10
+ENDPROCEDURE
+x <- 1
+y <- 2
+CALL Update(x, y)");
+
+    assert_eq!(interpreter.env().get_value("x"), Some(Value::Integer(1)));
+    assert_eq!(interpreter.env().get_value("y"), Some(Value::Integer(20)));
+}
+
+#[test]
+fn procedures_recurse() {
+    let interpreter = run("DECLARE total : INTEGER
+PROCEDURE Sum(n : INTEGER)
+  IF n > 0 THEN
+    total <- total + n
+    CALL Sum(n - 1)
+  ENDIF
+ENDPROCEDURE
+CALL Sum(4)");
+
+    assert_eq!(
+        interpreter.env().get_value("total"),
+        Some(Value::Integer(10))
+    );
+}
+
+#[test]
+fn functions_evaluate_inside_expressions() {
+    let interpreter = run("DECLARE f : INTEGER
+FUNCTION Factorial(n : INTEGER) RETURNS INTEGER
+  IF n <= 1
+    THEN
+      RETURN 1
+    ELSE
+      RETURN n * Factorial(n - 1)
+  ENDIF
+ENDFUNCTION
+f <- Factorial(5) + 1");
+
+    assert_eq!(interpreter.env().get_value("f"), Some(Value::Integer(121)));
+}
+
+#[test]
+fn subroutines_are_defined_before_running() {
+    let interpreter = run("DECLARE f : INTEGER
+DECLARE g : INTEGER
+f <- Twice(4)
+CALL Store(7)
+PROCEDURE Store(n : INTEGER)
+  g <- n
+ENDPROCEDURE
+FUNCTION Twice(n : INTEGER) RETURNS INTEGER
+  RETURN n * 2
+ENDFUNCTION");
+
+    assert_eq!(interpreter.env().get_value("f"), Some(Value::Integer(8)));
+    assert_eq!(interpreter.env().get_value("g"), Some(Value::Integer(7)));
+
+    // Definitions inside control flow only exist once they have run
+    let ast = parser()
+        .parse(
+            "IF FALSE
+  THEN
+    PROCEDURE Hidden()
+    ENDPROCEDURE
+ENDIF
+CALL Hidden()",
+        )
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::UndefinedProcedure(name)) if name == "Hidden"
+    ));
+
+    // `exec` registers a definition when it reaches it
+    let ast = parser()
+        .parse("DECLARE h : INTEGER\nFUNCTION Half(n : INTEGER) RETURNS INTEGER\n  RETURN n DIV 2\nENDFUNCTION\nh <- Half(9)")
+        .into_result()
+        .unwrap();
+    let mut interpreter = Interpreter::new();
+    for stmt in &ast {
+        interpreter.exec(stmt).unwrap();
+    }
+    assert_eq!(interpreter.env().get_value("h"), Some(Value::Integer(4)));
+}
+
+#[test]
+fn subroutines_cannot_be_defined_twice() {
+    let ast = parser()
+        .parse("PROCEDURE P()\nENDPROCEDURE\nFUNCTION P RETURNS INTEGER\n  RETURN 1\nENDFUNCTION")
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::AlreadyDeclared(name)) if name == "P"
+    ));
+}
+
+#[test]
+fn functions_must_return_on_every_path() {
+    let interpreter = run("DECLARE a : INTEGER
+DECLARE b : INTEGER
+FUNCTION Sign(n : INTEGER) RETURNS INTEGER
+  IF n >= 0
+    THEN
+      RETURN 1
+  ENDIF
+  IF n < 0
+    THEN
+      RETURN -1
+  ENDIF
+ENDFUNCTION
+FUNCTION FirstPositive(n : INTEGER) RETURNS INTEGER
+  WHILE TRUE
+    IF n > 0
+      THEN
+        RETURN n
+    ENDIF
+    n <- n + 1
+  ENDWHILE
+ENDFUNCTION
+a <- Sign(-4)
+b <- FirstPositive(-2)");
+
+    assert_eq!(interpreter.env().get_value("a"), Some(Value::Integer(-1)));
+    assert_eq!(interpreter.env().get_value("b"), Some(Value::Integer(1)));
+
+    let ast = parser()
+        .parse(
+            "FUNCTION F(n : INTEGER) RETURNS INTEGER
+  WHILE n > 0
+    RETURN n
+  ENDWHILE
+ENDFUNCTION
+OUTPUT F(0)",
+        )
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::MissingReturn(name)) if name == "F"
+    ));
+}
+
+#[test]
+fn functions_check_return_type() {
+    let ast = parser()
+        .parse("FUNCTION F RETURNS INTEGER\n  RETURN \"one\"\nENDFUNCTION\nOUTPUT F()")
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::TypeMismatch {
+            expected: Type::Integer,
+            found: Type::String
+        })
+    ));
+}
+
+#[test]
+fn arrays_with_arbitrary_bounds() {
+    let interpreter = run("DECLARE A : ARRAY[-1:1, 5:6] OF INTEGER
+DECLARE x : INTEGER
+PROCEDURE Double(BYREF n : INTEGER)
+  n <- n * 2
+ENDPROCEDURE
+A[-1, 6] <- 21
+CALL Double(A[-1, 6])
+x <- A[-1, 6]");
+
+    assert_eq!(interpreter.env().get_value("x"), Some(Value::Integer(42)));
+}
+
+#[test]
+fn arrays_check_bounds() {
+    let ast = parser()
+        .parse("DECLARE A : ARRAY[1:3] OF INTEGER\nA[4] <- 1")
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::IndexOutOfBounds {
+            array,
+            index: 4,
+            bounds: (1, 3)
+        }) if array == "A"
+    ));
+
+    for bounds in [
+        "1:99999999999999999999",
+        "1:9999999999999",
+        "-9223372036854775808:9223372036854775807",
+    ] {
+        let src = format!("DECLARE A : ARRAY[{}] OF INTEGER", bounds);
+        assert!(parser().parse(&src).has_errors(), "{}", bounds);
+    }
+}
+
+#[test]
+fn records_are_copied_on_assignment() {
+    let interpreter = run("TYPE Student
+  DECLARE Name : STRING
+  DECLARE Age : INTEGER
+ENDTYPE
+DECLARE Students : ARRAY[1:5] OF Student
+DECLARE S : Student
+DECLARE a : STRING
+DECLARE b : STRING
+Students[3].Name <- \"Ann\"
+S <- Students[3]
+S.Name <- \"Bob\"
+a <- Students[3].Name
+b <- S.Name");
+
+    assert_eq!(
+        interpreter.env().get_value("a"),
+        Some(Value::String("Ann".into()))
+    );
+    assert_eq!(
+        interpreter.env().get_value("b"),
+        Some(Value::String("Bob".into()))
+    );
+}
+
+#[test]
+fn records_cannot_contain_themselves() {
+    for src in [
+        "TYPE Node\n  DECLARE Next : Node\nENDTYPE",
+        "TYPE Node\n  DECLARE Children : ARRAY[1:2] OF Node\nENDTYPE",
+        "TYPE Leaf\n  DECLARE Up : Tree\nENDTYPE\nTYPE Tree\n  DECLARE Left : Leaf\nENDTYPE",
+    ] {
+        let ast = parser().parse(src).into_result().unwrap();
+        assert!(
+            matches!(Interpreter::new().run(&ast), Err(Error::CyclicType(_))),
+            "{}",
+            src
+        );
+    }
+
+    run("TYPE NodePtr = ^Node
+TYPE Node
+  DECLARE Value : INTEGER
+  DECLARE Next : NodePtr
+ENDTYPE
+DECLARE n : Node");
+}
+
+#[test]
+fn enums_order_by_declaration() {
+    let interpreter = run("TYPE Season = (Spring, Summer, Autumn, Winter)
+DECLARE s : Season
+DECLARE n : INTEGER
+DECLARE warm : INTEGER
+FOR s <- Summer TO Winter
+  n <- n + 1
+  CASE OF s
+    Spring TO Summer : warm <- warm + 1
+  ENDCASE
+NEXT s");
+
+    assert_eq!(interpreter.env().get_value("n"), Some(Value::Integer(3)));
+    assert_eq!(interpreter.env().get_value("warm"), Some(Value::Integer(1)));
+    assert_eq!(
+        interpreter.env().get("s").map(|s| s.to_string()),
+        Some("Winter".into())
+    );
+}
+
+#[test]
+fn enums_reject_shared_members() {
+    let ast = parser()
+        .parse("TYPE Season = (Spring, Summer)\nTYPE Term = (Autumn, Spring)")
+        .into_result()
+        .unwrap();
+
+    assert!(Interpreter::new().run(&ast).is_err());
+}
+
+#[test]
+fn pointers_to_variables_and_heap_cells() {
+    let interpreter = run("TYPE PInt = ^INTEGER
+TYPE PNode = ^Node
+TYPE Node
+  DECLARE Data : INTEGER
+  DECLARE Next : PNode
+ENDTYPE
+DECLARE x : INTEGER
+DECLARE p : PInt
+DECLARE Head : PNode
+DECLARE Current : PNode
+DECLARE Sum : INTEGER
+DECLARE i : INTEGER
+p <- @x
+p^ <- 5
+FOR i <- 1 TO 3
+  Current <- NEW Node
+  Current^.Data <- i
+  Current^.Next <- Head
+  Head <- Current
+NEXT i
+WHILE Current <> Head^.Next^.Next^.Next
+  Sum <- Sum + Current^.Data
+  Current <- Current^.Next
+ENDWHILE");
+
+    assert_eq!(interpreter.env().get_value("x"), Some(Value::Integer(5)));
+    assert_eq!(interpreter.env().get_value("Sum"), Some(Value::Integer(6)));
+}
+
+#[test]
+fn pointers_report_null_and_dangling_dereferences() {
+    let null = parser()
+        .parse("TYPE PInt = ^INTEGER\nDECLARE p : PInt\nDECLARE x : INTEGER\nx <- p^")
+        .into_result()
+        .unwrap();
+    let dangling = parser()
+        .parse(
+            "TYPE PInt = ^INTEGER
+DECLARE p : PInt
+PROCEDURE Leak()
+  DECLARE Local : INTEGER
+  p <- @Local
+ENDPROCEDURE
+PROCEDURE Reuse()
+  DECLARE Other : INTEGER
+ENDPROCEDURE
+CALL Leak()
+CALL Reuse()
+p^ <- 1",
+        )
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&null),
+        Err(Error::NullPointer(_))
+    ));
+    assert!(matches!(
+        Interpreter::new().run(&dangling),
+        Err(Error::DanglingPointer(_))
+    ));
+}
+
+#[test]
+fn sets_support_membership_and_set_operators() {
+    let interpreter = run("TYPE LetterSet = SET OF CHAR
+DEFINE Vowels ('a', 'e', 'i', 'o', 'u') : LetterSet
+DEFINE First ('a', 'b', 'c', 'd', 'e') : LetterSet
+DECLARE Both : LetterSet
+DECLARE Either : LetterSet
+DECLARE Consonants : LetterSet
+DECLARE IsVowel : BOOLEAN
+DECLARE Same : BOOLEAN
+Both <- Vowels INTERSECTION First
+Either <- Vowels UNION First
+Consonants <- First DIFFERENCE Vowels
+IsVowel <- 'e' IN Vowels AND NOT ('b' IN Vowels)
+Same <- Either = First UNION Vowels");
+
+    let elements = |name| interpreter.env().get(name).map(|s| s.to_string());
+    assert_eq!(elements("Both"), Some("(a, e)".into()));
+    assert_eq!(elements("Either"), Some("(a, e, i, o, u, b, c, d)".into()));
+    assert_eq!(elements("Consonants"), Some("(b, c, d)".into()));
+    assert_eq!(
+        interpreter.env().get_value("IsVowel"),
+        Some(Value::Boolean(true))
+    );
+    assert_eq!(
+        interpreter.env().get_value("Same"),
+        Some(Value::Boolean(true))
+    );
+}
+
+#[test]
+fn constants_are_folded_and_immutable() {
+    let interpreter = run("CONSTANT Rate = 2.5
+CONSTANT Max = 2 * 5
+DECLARE Area : REAL
+DECLARE Total : INTEGER
+Area <- Rate * 2 * 2
+FOR Total <- 1 TO Max
+NEXT Total");
+
+    assert_eq!(interpreter.env().get_value("Area"), Some(Value::Real(10.0)));
+    assert_eq!(interpreter.env().get_value("Max"), Some(Value::Integer(10)));
+
+    let assign = parser()
+        .parse("CONSTANT Max = 10\nMax <- 11")
+        .into_result()
+        .unwrap();
+    let byref = parser()
+        .parse(
+            "CONSTANT Max = 10
+PROCEDURE Bump(BYREF n : INTEGER)
+  n <- n + 1
+ENDPROCEDURE
+CALL Bump(Max)",
+        )
+        .into_result()
+        .unwrap();
+
+    assert!(matches!(
+        Interpreter::new().run(&assign),
+        Err(Error::AssignToConstant(_))
+    ));
+    assert!(matches!(
+        Interpreter::new().run(&byref),
+        Err(Error::AssignToConstant(_))
+    ));
+    assert!(parser()
+        .parse("DECLARE x : INTEGER\nCONSTANT y = x + 1")
+        .has_errors());
+}
+
+#[test]
+fn integer_arithmetic_fails_instead_of_overflowing() {
+    for src in [
+        "CONSTANT X = 1 DIV 0",
+        "CONSTANT Big = 9223372036854775807 + 1",
+    ] {
+        assert!(parser().parse(src).has_errors(), "{}", src);
+    }
+
+    let error = |src: &str| {
+        let ast = parser().parse(src).into_result().unwrap();
+        match Interpreter::new().run(&ast) {
+            Err(Error::EvaluationError(EvalError::ValueError(e))) => e,
+            result => panic!("{}: {:?}", src, result),
+        }
+    };
+
+    assert!(matches!(
+        error("OUTPUT 7 MOD 0"),
+        ValueError::DivisionByZero
+    ));
+    assert!(matches!(
+        error("CASE OF 1\n  1 DIV 0 : OUTPUT 1\nENDCASE"),
+        ValueError::DivisionByZero
+    ));
+    assert!(matches!(
+        error("OUTPUT (-9223372036854775807 - 1) DIV -1"),
+        ValueError::Overflow
+    ));
+    assert!(matches!(
+        error("OUTPUT 4611686018427387904 * 2"),
+        ValueError::Overflow
+    ));
+}
+
+#[test]
+fn input_converts_to_the_declared_type() {
+    let ast = parser()
+        .parse(
+            "DECLARE n : INTEGER
+DECLARE r : REAL
+DECLARE b : BOOLEAN
+DECLARE c : CHAR
+DECLARE s : STRING
+DECLARE d : DATE
+DECLARE Scores : ARRAY[1:2] OF INTEGER
+INPUT n
+INPUT r
+INPUT b
+INPUT c
+INPUT s
+INPUT d
+INPUT Scores[2]",
+        )
+        .into_result()
+        .unwrap();
+    let input = ScriptedInput::new(["-12", "3", "TRUE", "x", "Hello, world", "05/11/2024", "7"]);
+    let mut interpreter = Interpreter::new().with_input(input);
+    interpreter.run(&ast).unwrap();
+
+    let value = |name| interpreter.env().get(name).map(|v| v.to_string());
+    assert_eq!(interpreter.env().get_value("n"), Some(Value::Integer(-12)));
+    assert_eq!(interpreter.env().get_value("r"), Some(Value::Real(3.0)));
+    assert_eq!(value("b"), Some("true".into()));
+    assert_eq!(value("c"), Some("x".into()));
+    assert_eq!(value("s"), Some("Hello, world".into()));
+    assert_eq!(value("d"), Some("05/11/2024".into()));
+    assert_eq!(value("Scores"), Some("[0, 7]".into()));
+}
+
+#[test]
+fn input_rejects_values_of_the_wrong_type() {
+    let ast = parser()
+        .parse("DECLARE n : INTEGER\nINPUT n")
+        .into_result()
+        .unwrap();
+    let mut interpreter = Interpreter::new().with_input(ScriptedInput::new(["3.5"]));
+
+    assert!(matches!(
+        interpreter.run(&ast),
+        Err(Error::InvalidInput { .. })
+    ));
+}
+
+#[test]
+fn output_goes_to_the_configured_sink() {
+    let ast = parser()
+        .parse("DECLARE i : INTEGER\nFOR i <- 1 TO 3\n  OUTPUT \"Line\", i\nNEXT i")
+        .into_result()
+        .unwrap();
+    let (first, second) = (BufferSink::new(), BufferSink::new());
+    let mut interpreter = Interpreter::new().with_output(TeeSink(first.clone(), second.clone()));
+    interpreter.run(&ast).unwrap();
+
+    assert_eq!(first.transcript(), "Line 1\nLine 2\nLine 3\n");
+    assert_eq!(second.lines(), first.lines());
+}
+
+#[test]
+fn sequential_files_round_trip() {
+    let ast = parser()
+        .parse(
+            "DECLARE Line : STRING
+DECLARE Count : INTEGER
+OPENFILE \"out.txt\" FOR WRITE
+WRITEFILE \"out.txt\", \"first\"
+CLOSEFILE \"out.txt\"
+OPENFILE \"out.txt\" FOR APPEND
+WRITEFILE \"out.txt\", 2
+CLOSEFILE \"out.txt\"
+OPENFILE \"out.txt\" FOR READ
+WHILE NOT EOF(\"out.txt\")
+  READFILE \"out.txt\", Line
+  Count <- Count + 1
+ENDWHILE
+CLOSEFILE \"out.txt\"",
+        )
+        .into_result()
+        .unwrap();
+    let fs = MemoryFileSystem::new();
+    let mut interpreter = Interpreter::new().with_file_system(fs.clone());
+    interpreter.run(&ast).unwrap();
+
+    assert_eq!(fs.contents("out.txt"), Some(b"first\n2\n".to_vec()));
+    assert_eq!(
+        interpreter.env().get_value("Count"),
+        Some(Value::Integer(2))
+    );
+    assert_eq!(
+        interpreter.env().get_value("Line"),
+        Some(Value::String("2".into()))
+    );
+}
+
+#[test]
+fn sequential_files_check_modes_and_open_state() {
+    let run = |src: &str| {
+        let ast = parser().parse(src).into_result().unwrap();
+        let fs = MemoryFileSystem::new();
+        fs.insert("in.txt", "line\n");
+        Interpreter::new().with_file_system(fs).run(&ast)
+    };
+
+    assert!(matches!(
+        run("DECLARE s : STRING\nOPENFILE \"a\" FOR WRITE\nREADFILE \"a\", s"),
+        Err(Error::WrongFileMode { .. })
+    ));
+    assert!(matches!(
+        run("OPENFILE \"in.txt\" FOR READ\nOPENFILE \"in.txt\" FOR READ"),
+        Err(Error::FileAlreadyOpen(_))
+    ));
+    assert!(matches!(
+        run("OPENFILE \"in.txt\" FOR READ"),
+        Err(Error::FilesLeftOpen(_))
+    ));
+}
+
+#[test]
+fn random_files_round_trip_records_between_runs() {
+    let types = "TYPE Pupil
+  DECLARE Name : STRING
+  DECLARE Mark : INTEGER
+  DECLARE Passed : BOOLEAN
+ENDTYPE
+DECLARE P : Pupil
+";
+    let write = format!(
+        "{}OPENFILE \"pupils.dat\" FOR RANDOM
+P.Name <- \"Ann\"
+P.Mark <- 71
+P.Passed <- TRUE
+SEEK \"pupils.dat\", 3
+PUTRECORD \"pupils.dat\", P
+CLOSEFILE \"pupils.dat\"",
+        types
+    );
+    let read = format!(
+        "{}DECLARE Blank : Pupil
+OPENFILE \"pupils.dat\" FOR RANDOM
+SEEK \"pupils.dat\", 2
+GETRECORD \"pupils.dat\", Blank
+GETRECORD \"pupils.dat\", P
+CLOSEFILE \"pupils.dat\"",
+        types
+    );
+
+    let fs = MemoryFileSystem::new();
+    let run = |src: &str| {
+        let ast = parser().parse(src).into_result().unwrap();
+        let mut interpreter = Interpreter::new().with_file_system(fs.clone());
+        interpreter.run(&ast).unwrap();
+        interpreter
+    };
+    run(&write);
+    let interpreter = run(&read);
+
+    // Each record is a 256 byte STRING, an 8 byte INTEGER and a 1 byte BOOLEAN
+    assert_eq!(fs.contents("pupils.dat").map(|c| c.len()), Some(3 * 265));
+    assert_eq!(
+        interpreter.env().get("P").map(|p| p.to_string()),
+        Some("{Name: Ann, Mark: 71, Passed: true}".into())
+    );
+    assert_eq!(
+        interpreter.env().get("Blank").map(|p| p.to_string()),
+        Some("{Name: , Mark: 0, Passed: false}".into())
+    );
+}
+
+#[test]
+fn random_files_reject_unreachable_addresses() {
+    for stmt in ["GETRECORD \"r.dat\", n", "PUTRECORD \"r.dat\", n"] {
+        let src = format!(
+            "DECLARE n : INTEGER
+OPENFILE \"r.dat\" FOR RANDOM
+SEEK \"r.dat\", 9223372036854775807
+{}",
+            stmt
+        );
+        let ast = parser().parse(&src).into_result().unwrap();
+        let mut interpreter = Interpreter::new().with_file_system(MemoryFileSystem::new());
+
+        assert!(
+            matches!(
+                interpreter.run(&ast),
+                Err(Error::InvalidAddress(9223372036854775807))
+            ),
+            "{}",
+            stmt
+        );
+    }
+}
+
+#[test]
+fn classes_inherit_override_and_share_objects() {
+    let ast = parser()
+        .parse(
+            "CLASS Pet
+  PRIVATE Name : STRING
+  PUBLIC Legs : INTEGER
+  PUBLIC PROCEDURE NEW(GivenName : STRING)
+    Name <- GivenName
+    Legs <- 4
+  ENDPROCEDURE
+  PUBLIC FUNCTION GetName() RETURNS STRING
+    RETURN Name
+  ENDFUNCTION
+  PUBLIC FUNCTION Speak() RETURNS STRING
+    RETURN \"...\"
+  ENDFUNCTION
+  PUBLIC FUNCTION Describe() RETURNS STRING
+    RETURN Speak()
+  ENDFUNCTION
+ENDCLASS
+CLASS Cat INHERITS Pet
+  PRIVATE Lives : INTEGER
+  PUBLIC PROCEDURE NEW(GivenName : STRING)
+    CALL SUPER.NEW(GivenName)
+    Lives <- 9
+  ENDPROCEDURE
+  PUBLIC FUNCTION Speak() RETURNS STRING
+    RETURN \"Meow\"
+  ENDFUNCTION
+ENDCLASS
+DECLARE Rex : Pet
+DECLARE Tom : Pet
+DECLARE Alias : Pet
+Rex <- NEW Pet(\"Rex\")
+Tom <- NEW Cat(\"Tom\")
+Alias <- Tom
+Alias.Legs <- 3
+OUTPUT Rex.GetName(), Rex.Describe()
+OUTPUT Tom.GetName(), Tom.Describe()
+OUTPUT Tom.Legs",
+        )
+        .into_result()
+        .unwrap();
+    let output = BufferSink::new();
+    Interpreter::new()
+        .with_output(output.clone())
+        .run(&ast)
+        .unwrap();
+
+    assert_eq!(output.transcript(), "Rex ...\nTom Meow\n3\n");
+}
+
+#[test]
+fn classes_hide_private_members() {
+    let class = "CLASS Account
+  PRIVATE Balance : INTEGER
+  PRIVATE PROCEDURE Audit()
+  ENDPROCEDURE
+ENDCLASS
+DECLARE a : Account
+a <- NEW Account";
+    let read = format!("{}\nOUTPUT a.Balance", class);
+    let call = format!("{}\nCALL a.Audit()", class);
+    let run = |src: &str| Interpreter::new().run(&parser().parse(src).into_result().unwrap());
+
+    assert!(matches!(run(&read), Err(Error::PrivateMember { .. })));
+    assert!(matches!(run(&call), Err(Error::PrivateMember { .. })));
+
+    // Subclasses inherit PRIVATE members without being able to reach them
+    for body in [
+        "OUTPUT Balance",
+        "Balance <- 1",
+        "CALL Audit()",
+        "CALL SUPER.Audit()",
+    ] {
+        let src = format!(
+            "{}
+CLASS Savings INHERITS Account
+  PUBLIC PROCEDURE Peek()
+    {}
+  ENDPROCEDURE
+ENDCLASS
+DECLARE s : Savings
+s <- NEW Savings
+CALL s.Peek()",
+            class, body
+        );
+        assert!(
+            matches!(run(&src), Err(Error::PrivateMember { class, .. }) if class == "Account"),
+            "{}",
+            body
+        );
+    }
+    assert!(matches!(
+        run("CLASS Empty\nENDCLASS\nDECLARE e : Empty\nOUTPUT e.Size()"),
+        Err(Error::NullPointer(_))
+    ));
+}
+
+#[test]
+fn string_builtins_index_from_one() {
+    let ast = parser()
+        .parse(
+            "DECLARE s : STRING
+s <- \"Hello World\"
+OUTPUT LEFT(s, 5), RIGHT(s, 5), MID(s, 7, 3), LENGTH(s)
+OUTPUT UCASE('a'), LCASE('Q'), TO_UPPER(s), TO_LOWER(\"ABC\")
+OUTPUT LEFT(s, 0), MID(s, 11, 1)",
+        )
+        .into_result()
+        .unwrap();
+    let output = BufferSink::new();
+    Interpreter::new()
+        .with_output(output.clone())
+        .run(&ast)
+        .unwrap();
+
+    assert_eq!(
+        output.lines(),
+        ["Hello World Wor 11", "A q HELLO WORLD abc", " d"]
+    );
+
+    for call in [
+        "MID(\"abc\", 0, 1)",
+        "MID(\"abc\", 2, 3)",
+        "RIGHT(\"abc\", 4)",
+        "MID(\"abc\", 9223372036854775807, 5)",
+        "RIGHT(\"abc\", -9223372036854775807 - 1)",
+    ] {
+        let src = format!("OUTPUT {}", call);
+        let ast = parser().parse(&src).into_result().unwrap();
+        assert!(matches!(
+            Interpreter::new().run(&ast),
+            Err(Error::SubstringOutOfRange { .. })
+        ));
+    }
+}
+
+#[test]
+fn conversion_builtins_follow_literal_rules() {
+    let interpreter = run("DECLARE i : INTEGER
+DECLARE r : REAL
+DECLARE c : CHAR
+DECLARE ok : BOOLEAN
+DECLARE bad : BOOLEAN
+DECLARE s : STRING
+DECLARE t : STRING
+DECLARE big : INTEGER
+i <- STR_TO_NUM(\"-42\") + INT(7.9) + ASC('A')
+r <- STR_TO_NUM(\"2.5\")
+c <- CHR(98)
+ok <- IS_NUM(\"12.0\")
+bad <- IS_NUM(\"12a\")
+s <- NUM_TO_STR(17)
+t <- NUM_TO_STR(3.0)
+big <- INT(9007199254740993)");
+
+    let value = |name| interpreter.env().get_value(name);
+    assert_eq!(value("i"), Some(Value::Integer(30)));
+    assert_eq!(value("r"), Some(Value::Real(2.5)));
+    assert_eq!(value("c"), Some(Value::Character('b')));
+    assert_eq!(value("ok"), Some(Value::Boolean(true)));
+    assert_eq!(value("bad"), Some(Value::Boolean(false)));
+    assert_eq!(value("s"), Some(Value::String("17".to_owned())));
+    assert_eq!(value("t"), Some(Value::String("3.0".to_owned())));
+    assert_eq!(value("big"), Some(Value::Integer(9007199254740993)));
+
+    let ast = parser()
+        .parse("OUTPUT STR_TO_NUM(\"ten\")")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::NotANumber(_))
+    ));
+
+    // A whole REAL reads back as a REAL, not an INTEGER
+    let ast = parser()
+        .parse("DECLARE x : INTEGER\nx <- STR_TO_NUM(NUM_TO_STR(3.0))")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::TypeMismatch {
+            expected: Type::Integer,
+            found: Type::Real
+        })
+    ));
+}
+
+#[test]
+fn rand_is_reproducible_with_a_seed() {
+    let ast = parser()
+        .parse(
+            "DECLARE i : INTEGER
+FOR i <- 1 TO 5
+  OUTPUT RAND(10)
+NEXT i",
+        )
+        .into_result()
+        .unwrap();
+    let rolls = |seed| {
+        let output = BufferSink::new();
+        Interpreter::new()
+            .with_seed(seed)
+            .with_output(output.clone())
+            .run(&ast)
+            .unwrap();
+        output.lines()
+    };
+
+    assert_eq!(rolls(3), rolls(3));
+    assert_ne!(rolls(3), rolls(4));
+    for roll in rolls(3) {
+        let roll: f64 = roll.parse().unwrap();
+        assert!((0.0..10.0).contains(&roll));
+    }
+}
+
+#[test]
+fn date_builtins_and_arithmetic() {
+    let ast = parser()
+        .parse(
+            "DECLARE Today : DATE
+DECLARE Due : DATE
+Today <- NOW()
+Due <- Today + 20
+OUTPUT DAY(Due), MONTH(Due), YEAR(Due), DAYINDEX(Today)
+OUTPUT Due - Today, SETDATE(29, 2, 2024) - 1 = `28/02/2024`
+OUTPUT SETDATE(1, 2, 2024)",
+        )
+        .into_result()
+        .unwrap();
+    let output = BufferSink::new();
+    Interpreter::new()
+        .with_today(date!(2024 - 03 - 15))
+        .with_output(output.clone())
+        .run(&ast)
+        .unwrap();
+
+    assert_eq!(output.lines(), ["4 4 2024 6", "20 true", "01/02/2024"]);
+
+    let ast = parser()
+        .parse("OUTPUT NOW() + 9223372036854775807")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::EvaluationError(EvalError::ValueError(
+            ValueError::DateOutOfRange
+        )))
+    ));
+
+    let ast = parser()
+        .parse("OUTPUT SETDATE(29, 2, 2023)")
+        .into_result()
+        .unwrap();
+    assert!(matches!(
+        Interpreter::new().run(&ast),
+        Err(Error::InvalidDate { .. })
+    ));
+}
+
+#[test]
+fn constants_stand_in_for_literals() {
+    let interpreter = run("CONSTANT N = 5
+CONSTANT Twice = N * 2
+DECLARE A : ARRAY[1:N] OF INTEGER
+A[N] <- Twice");
+
+    assert_eq!(
+        interpreter.env().get_value("A").map(|a| a.to_string()),
+        Some("[0, 0, 0, 0, 10]".to_owned())
+    );
+
+    assert!(parser()
+        .parse("DECLARE N : INTEGER\nDECLARE A : ARRAY[1:N] OF INTEGER")
+        .has_errors());
+    assert!(parser()
+        .parse("CONSTANT N = 2.5\nDECLARE A : ARRAY[1:N] OF INTEGER")
+        .has_errors());
+}
+
+#[test]
+fn constants_follow_scope() {
+    let ast = parser()
+        .parse(
+            "CONSTANT Max = 10
+PROCEDURE Param(Max : INTEGER)
+  OUTPUT Max
+ENDPROCEDURE
+PROCEDURE Local()
+  DECLARE Max : INTEGER
+  Max <- 5
+  OUTPUT Max
+ENDPROCEDURE
+PROCEDURE Inner()
+  CONSTANT K = 1
+  DECLARE A : ARRAY[1:K] OF INTEGER
+  OUTPUT K
+ENDPROCEDURE
+CALL Param(3)
+CALL Local()
+CALL Inner()
+DECLARE K : INTEGER
+K <- 7
+OUTPUT K, Max",
+        )
+        .into_result()
+        .unwrap();
+    let output = BufferSink::new();
+    Interpreter::new()
+        .with_output(output.clone())
+        .run(&ast)
+        .unwrap();
+
+    assert_eq!(output.lines(), ["3", "5", "1", "7 10"]);
+
+    // A bound names whatever is in scope, which may hide a CONSTANT
+    assert!(parser()
+        .parse(
+            "CONSTANT N = 3
+PROCEDURE P(N : INTEGER)
+  DECLARE A : ARRAY[1:N] OF INTEGER
+ENDPROCEDURE"
+        )
+        .has_errors());
+}